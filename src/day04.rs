@@ -19,57 +19,190 @@ fn parse_nums(s: &str) -> IResult<&str, BitSet> {
     .parse(s)
 }
 
-fn parse(s: &str) -> IResult<&str, usize> {
-    let (s, _) = tag("Card")(s)?;
-    let (s, _) = space1(s)?;
-    let (s, _) = nom_usize(s)?;
-    let (s, _) = tag(":")(s)?;
-    let (s, _) = space1(s)?;
-    let (s, left) = parse_nums(s)?;
-    let (s, _) = delimited(space1, tag("|"), space1).parse(s)?;
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Card {
+    pub id: usize,
+    pub winning: BitSet,
+    pub held: BitSet,
+}
 
-    let (s, count) = fold_separated_list0(
-        space1,
-        nom_usize,
-        || 0,
-        |acc, n| acc + usize::from(left.contains(n)),
-    )
-    .parse(s)?;
+impl Card {
+    pub fn matches(&self) -> usize {
+        self.winning.intersection(&self.held).count()
+    }
 
-    Ok((s, count))
+    // None once the points no longer fit in a usize.
+    pub fn points(&self) -> Option<usize> {
+        match self.matches() {
+            0 => Some(0),
+            n => u32::try_from(n - 1)
+                .ok()
+                .and_then(|shift| 1usize.checked_shl(shift)),
+        }
+    }
 }
 
-#[aoc_generator(day4)]
-pub fn generator(input: &str) -> Vec<usize> {
-    process_input(nom_lines(parse))(input)
+#[derive(Debug, Clone, Copy)]
+pub enum CopyRule {
+    Matches,
+    Points,
+    Custom(fn(&Card) -> usize),
 }
 
-fn check_part1(n: usize) -> usize {
-    if n == 0 {
-        0
-    } else {
-        2usize.pow(u32::try_from(n - 1).unwrap())
+impl CopyRule {
+    fn copies(&self, card: &Card) -> Result<usize, CascadeError> {
+        match self {
+            CopyRule::Matches => Ok(card.matches()),
+            CopyRule::Points => card.points().ok_or(CascadeError::PointsOverflow {
+                id: card.id,
+                matches: card.matches(),
+            }),
+            CopyRule::Custom(f) => Ok(f(card)),
+        }
     }
 }
 
-#[aoc(day4, part1)]
-pub fn part1(inputs: &[usize]) -> usize {
-    inputs.iter().map(|a| check_part1(*a)).sum()
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Overflow {
+    #[default]
+    Clamp,
+    Error,
 }
 
-#[aoc(day4, part2)]
-pub fn part2(inputs: &[usize]) -> usize {
-    let mut cards = vec![1; inputs.len()];
+#[derive(Debug, PartialEq, Eq)]
+pub enum CascadeError {
+    PastEnd {
+        id: usize,
+        wanted: usize,
+        available: usize,
+    },
+    InitialLength {
+        expected: usize,
+        found: usize,
+    },
+    // too many matches for the points to fit in a usize
+    PointsOverflow {
+        id: usize,
+        matches: usize,
+    },
+    // the copies won from this card no longer fit in a usize
+    CopiesOverflow {
+        id: usize,
+    },
+}
 
-    for (i, &count) in inputs.iter().enumerate() {
-        let multipler = cards[i];
+#[derive(Debug, Clone)]
+pub struct Cascade {
+    pub rule: CopyRule,
+    pub overflow: Overflow,
+    pub initial: Option<Vec<usize>>,
+}
 
-        for n in 0..count {
-            cards[i + 1 + n] += multipler;
+impl Default for Cascade {
+    fn default() -> Self {
+        Self {
+            rule: CopyRule::Matches,
+            overflow: Overflow::Clamp,
+            initial: None,
         }
     }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CardCount {
+    pub id: usize,
+    pub won: usize,
+    pub copies: usize,
+}
 
-    cards.iter().sum::<usize>()
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CascadeResult {
+    pub cards: Vec<CardCount>,
+    pub total: usize,
+}
+
+impl Cascade {
+    pub fn run(&self, cards: &[Card]) -> Result<CascadeResult, CascadeError> {
+        let mut copies = match &self.initial {
+            Some(initial) if initial.len() != cards.len() => {
+                return Err(CascadeError::InitialLength {
+                    expected: cards.len(),
+                    found: initial.len(),
+                });
+            }
+            Some(initial) => initial.clone(),
+            None => vec![1; cards.len()],
+        };
+
+        let mut counts = Vec::with_capacity(cards.len());
+        for (i, card) in cards.iter().enumerate() {
+            let won = self.rule.copies(card)?;
+            let available = cards.len() - i - 1;
+
+            if won > available && self.overflow == Overflow::Error {
+                return Err(CascadeError::PastEnd {
+                    id: card.id,
+                    wanted: won,
+                    available,
+                });
+            }
+
+            let multiplier = copies[i];
+            for c in &mut copies[i + 1..=i + won.min(available)] {
+                *c = c
+                    .checked_add(multiplier)
+                    .ok_or(CascadeError::CopiesOverflow { id: card.id })?;
+            }
+
+            counts.push(CardCount {
+                id: card.id,
+                won,
+                copies: multiplier,
+            });
+        }
+
+        let total = cards
+            .iter()
+            .zip(&copies)
+            .try_fold(0usize, |total, (card, &c)| {
+                total
+                    .checked_add(c)
+                    .ok_or(CascadeError::CopiesOverflow { id: card.id })
+            })?;
+
+        Ok(CascadeResult {
+            total,
+            cards: counts,
+        })
+    }
+}
+
+fn parse(s: &str) -> IResult<&str, Card> {
+    let (s, _) = tag("Card")(s)?;
+    let (s, _) = space1(s)?;
+    let (s, id) = nom_usize(s)?;
+    let (s, _) = tag(":")(s)?;
+    let (s, _) = space1(s)?;
+    let (s, winning) = parse_nums(s)?;
+    let (s, _) = delimited(space1, tag("|"), space1).parse(s)?;
+    let (s, held) = parse_nums(s)?;
+
+    Ok((s, Card { id, winning, held }))
+}
+
+#[aoc_generator(day4)]
+pub fn generator(input: &str) -> Vec<Card> {
+    process_input(nom_lines(parse))(input)
+}
+
+#[aoc(day4, part1)]
+pub fn part1(inputs: &[Card]) -> usize {
+    inputs.iter().map(|card| card.points().unwrap()).sum()
+}
+
+#[aoc(day4, part2)]
+pub fn part2(inputs: &[Card]) -> usize {
+    Cascade::default().run(inputs).unwrap().total
 }
 
 #[cfg(test)]
@@ -100,6 +233,84 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
         assert_eq!(part2(&generator(SAMPLE)), 30);
     }
 
+    #[test]
+    pub fn cascade_test() {
+        let cards = generator(SAMPLE);
+        let res = Cascade::default().run(&cards).unwrap();
+        assert_eq!(
+            res.cards.iter().map(|c| c.copies).collect::<Vec<_>>(),
+            [1, 2, 4, 8, 14, 1]
+        );
+
+        let res = Cascade {
+            initial: Some(vec![1, 0, 0, 0, 0, 0]),
+            ..Cascade::default()
+        }
+        .run(&cards)
+        .unwrap();
+        assert_eq!(res.total, 15);
+
+        let cascade = Cascade {
+            rule: CopyRule::Points,
+            overflow: Overflow::Error,
+            initial: None,
+        };
+        assert_eq!(
+            cascade.run(&cards),
+            Err(CascadeError::PastEnd {
+                id: 1,
+                wanted: 8,
+                available: 5
+            })
+        );
+
+        let many = Card {
+            id: 7,
+            winning: (1..=65).collect(),
+            held: (1..=65).collect(),
+        };
+        assert_eq!(many.points(), None);
+        assert_eq!(
+            cascade.run(&[many]),
+            Err(CascadeError::PointsOverflow { id: 7, matches: 65 })
+        );
+
+        // each card quadruples the copies of the next three
+        let growing: Vec<_> = (1..=80)
+            .map(|id| Card {
+                id,
+                winning: (1..=3).collect(),
+                held: (1..=3).collect(),
+            })
+            .collect();
+        let cascade = Cascade {
+            rule: CopyRule::Points,
+            ..Cascade::default()
+        };
+        assert!(matches!(
+            cascade.run(&growing),
+            Err(CascadeError::CopiesOverflow { .. })
+        ));
+        assert!(cascade.run(&growing[..20]).is_ok());
+
+        // the per-card counts fit but their sum doesn't
+        let losing: Vec<_> = (1..=2)
+            .map(|id| Card {
+                id,
+                winning: (1..=3).collect(),
+                held: (4..=6).collect(),
+            })
+            .collect();
+        let cascade = Cascade {
+            initial: Some(vec![usize::MAX, 1]),
+            ..Cascade::default()
+        };
+        assert_eq!(
+            cascade.run(&losing),
+            Err(CascadeError::CopiesOverflow { id: 2 })
+        );
+    }
+
     mod regression {
         use super::*;
 
@@ -1,9 +1,12 @@
-use std::ops::Range;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
 
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 
-use crate::common::{range_intersect, utils::parse_split};
+use crate::common::utils::parse_split;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct IndividualMapper {
@@ -21,46 +24,271 @@ impl IndividualMapper {
 
 #[derive(Debug, Clone)]
 pub struct GroupMapper {
+    from: String,
+    to: String,
     mappers: Vec<IndividualMapper>,
 }
 
 impl GroupMapper {
-    fn apply_range(
+    pub fn to_interval_map(&self) -> IntervalMap {
+        let mut sources = self.mappers.iter().collect_vec();
+        sources.sort_unstable_by_key(|m| m.source.start);
+
+        let mut segments = vec![];
+        let mut pos = 0;
+        for mapper in sources {
+            if mapper.source.start > pos {
+                segments.push((pos, 0));
+            }
+            segments.push((
+                mapper.source.start,
+                mapper.destination as isize - mapper.source.start as isize,
+            ));
+            pos = mapper.source.end;
+        }
+        segments.push((pos, 0));
+
+        IntervalMap::from_segments(segments)
+    }
+}
+
+// Piecewise-linear map over `usize`. Each segment starts at `.0` and runs up to
+// the next segment's start, mapping `x` to `x + .1`. The last segment is unbounded.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IntervalMap {
+    segments: Vec<(usize, isize)>,
+}
+
+impl Default for IntervalMap {
+    fn default() -> Self {
+        Self {
+            segments: vec![(0, 0)],
+        }
+    }
+}
+
+impl IntervalMap {
+    fn from_segments(segments: Vec<(usize, isize)>) -> Self {
+        let mut merged: Vec<(usize, isize)> = Vec::with_capacity(segments.len());
+        for (start, offset) in segments {
+            match merged.last_mut() {
+                Some(last) if last.0 == start => last.1 = offset,
+                Some(last) if last.1 == offset => {}
+                _ => merged.push((start, offset)),
+            }
+        }
+
+        Self { segments: merged }
+    }
+
+    fn end(&self, i: usize) -> usize {
+        self.segments.get(i + 1).map_or(usize::MAX, |s| s.0)
+    }
+
+    fn segment(&self, x: usize) -> usize {
+        self.segments.partition_point(|s| s.0 <= x) - 1
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.segments.iter().map(|s| s.0)
+    }
+
+    pub fn apply(&self, x: usize) -> usize {
+        x.wrapping_add_signed(self.segments[self.segment(x)].1)
+    }
+
+    // Splits `range` on the breakpoints, yielding each (domain, image) piece.
+    pub fn pieces(
         &self,
-        input: &mut Vec<Range<usize>>,
-        temp: &mut Vec<Range<usize>>,
-        output: &mut Vec<Range<usize>>,
-    ) {
-        for mapper in &self.mappers {
-            while let Some(range) = input.pop() {
-                let [before, inter, after] = range_intersect(range, &mapper.source);
-
-                if let Some(before) = before {
-                    temp.push(before);
-                }
-
-                if let Some(inter) = inter {
-                    output.push(
-                        (inter.start - mapper.source.start + mapper.destination)
-                            ..(inter.end - mapper.source.start + mapper.destination),
-                    );
-                }
-
-                if let Some(after) = after {
-                    temp.push(after);
-                }
+        range: Range<usize>,
+    ) -> impl Iterator<Item = (Range<usize>, Range<usize>)> {
+        let first = self.segment(range.start);
+        (first..self.segments.len())
+            .map_while(move |i| {
+                let (start, offset) = self.segments[i];
+                let piece = start.max(range.start)..self.end(i).min(range.end);
+                (piece.start < range.end).then_some((piece, offset))
+            })
+            .map(|(piece, offset)| {
+                let image =
+                    piece.start.wrapping_add_signed(offset)..piece.end.wrapping_add_signed(offset);
+                (piece, image)
+            })
+    }
+
+    pub fn apply_range(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        self.pieces(range).map(|(_, image)| image).collect()
+    }
+
+    // Returns the input with the smallest image in `range`, along with that image.
+    pub fn min_image(&self, range: Range<usize>) -> Option<(usize, usize)> {
+        self.pieces(range)
+            .map(|(piece, image)| (piece.start, image.start))
+            .min_by_key(|&(_, image)| image)
+    }
+
+    // Returns the map equivalent to applying `self` and then `other`.
+    pub fn compose(&self, other: &IntervalMap) -> IntervalMap {
+        let mut segments = vec![];
+        for (i, &(start, offset)) in self.segments.iter().enumerate() {
+            let end = self.end(i);
+            let image_end = if end == usize::MAX {
+                usize::MAX
+            } else {
+                end.wrapping_add_signed(offset)
+            };
+
+            for (piece, _) in other.pieces(start.wrapping_add_signed(offset)..image_end) {
+                let second = other.segments[other.segment(piece.start)].1;
+                segments.push((piece.start.wrapping_add_signed(-offset), offset + second));
             }
-            std::mem::swap(input, temp);
         }
 
-        // ranges that don't map
-        output.append(input);
+        IntervalMap::from_segments(segments)
+    }
+
+    // Returns `None` unless the map is a bijection on `usize`.
+    pub fn inverse(&self) -> Option<IntervalMap> {
+        let last = self.segments.len() - 1;
+        if self.segments[last].1 != 0 {
+            return None;
+        }
+
+        let mut images = (0..self.segments.len())
+            .map(|i| {
+                let (start, offset) = self.segments[i];
+                let end = if i == last {
+                    usize::MAX
+                } else {
+                    self.end(i).wrapping_add_signed(offset)
+                };
+                (start.wrapping_add_signed(offset), end, -offset)
+            })
+            .collect_vec();
+        images.sort_unstable();
+
+        let mut pos = 0;
+        for &(start, end, _) in &images {
+            if start != pos {
+                return None;
+            }
+            pos = end;
+        }
+
+        Some(IntervalMap::from_segments(
+            images
+                .into_iter()
+                .map(|(start, _, offset)| (start, offset))
+                .collect(),
+        ))
+    }
+
+    pub fn compose_all<'a>(mappers: impl IntoIterator<Item = &'a GroupMapper>) -> IntervalMap {
+        mappers.into_iter().fold(IntervalMap::default(), |acc, gm| {
+            acc.compose(&gm.to_interval_map())
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RouteError {
+    DuplicateSource(String),
+    DuplicateDestination(String),
+    NoStart,
+    Disconnected(String),
+    UnknownCategory(String),
+    NotInvertible,
+}
+
+#[derive(Debug, Clone)]
+pub struct Almanac {
+    seeds: Vec<usize>,
+    stages: Vec<GroupMapper>,
+}
+
+impl Almanac {
+    // Orders the stages into a single chain, checking that every category is
+    // produced at most once and consumed at most once.
+    pub fn new(seeds: Vec<usize>, stages: Vec<GroupMapper>) -> Result<Self, RouteError> {
+        let mut by_source = HashMap::new();
+        let mut destinations = HashSet::new();
+        for (i, stage) in stages.iter().enumerate() {
+            if by_source.insert(stage.from.as_str(), i).is_some() {
+                return Err(RouteError::DuplicateSource(stage.from.clone()));
+            }
+            if !destinations.insert(stage.to.as_str()) {
+                return Err(RouteError::DuplicateDestination(stage.to.clone()));
+            }
+        }
+
+        let mut order = Vec::with_capacity(stages.len());
+        if !stages.is_empty() {
+            let mut current = stages
+                .iter()
+                .map(|stage| stage.from.as_str())
+                .find(|from| !destinations.contains(from))
+                .ok_or(RouteError::NoStart)?;
+
+            while let Some(&i) = by_source.get(current) {
+                order.push(i);
+                current = &stages[i].to;
+            }
+        }
+
+        if let Some(stage) = stages.iter().enumerate().find(|(i, _)| !order.contains(i)) {
+            return Err(RouteError::Disconnected(stage.1.from.clone()));
+        }
+
+        let mut stages = stages.into_iter().map(Some).collect_vec();
+        let stages = order
+            .into_iter()
+            .map(|i| stages[i].take().unwrap())
+            .collect();
+
+        Ok(Self { seeds, stages })
+    }
+
+    pub fn categories(&self) -> impl Iterator<Item = &str> {
+        self.stages
+            .first()
+            .map(|stage| stage.from.as_str())
+            .into_iter()
+            .chain(self.stages.iter().map(|stage| stage.to.as_str()))
+    }
+
+    fn position(&self, category: &str) -> Result<usize, RouteError> {
+        self.categories()
+            .position(|c| c == category)
+            .ok_or_else(|| RouteError::UnknownCategory(category.to_string()))
+    }
+
+    pub fn route(&self, from: &str, to: &str) -> Result<IntervalMap, RouteError> {
+        let (start, end) = (self.position(from)?, self.position(to)?);
+
+        if start <= end {
+            Ok(IntervalMap::compose_all(&self.stages[start..end]))
+        } else {
+            IntervalMap::compose_all(&self.stages[end..start])
+                .inverse()
+                .ok_or(RouteError::NotInvertible)
+        }
+    }
+
+    pub fn map_value(&self, from: &str, to: &str, value: usize) -> Result<usize, RouteError> {
+        self.route(from, to).map(|map| map.apply(value))
     }
 }
 
 fn parse(group: &str) -> GroupMapper {
+    let mut lines = group.lines();
+    let (from, to) = lines
+        .next()
+        .and_then(|header| header.strip_suffix(" map:"))
+        .and_then(|header| header.split_once("-to-"))
+        .unwrap();
+
     let mut mappers = vec![];
-    for line in group.lines().skip(1) {
+    for line in lines {
         let (a, b, c) = line
             .split(' ')
             .map(|x| x.parse::<usize>().unwrap())
@@ -74,11 +302,15 @@ fn parse(group: &str) -> GroupMapper {
             destination: a,
         });
     }
-    GroupMapper { mappers }
+    GroupMapper {
+        from: from.to_string(),
+        to: to.to_string(),
+        mappers,
+    }
 }
 
 #[aoc_generator(day5)]
-pub fn generator(input: &str) -> (Vec<usize>, Vec<GroupMapper>) {
+pub fn generator(input: &str) -> Almanac {
     let mut groups = input.split("\n\n");
 
     let (_, seeds_s) = groups.next().unwrap().split_once(':').unwrap();
@@ -86,7 +318,7 @@ pub fn generator(input: &str) -> (Vec<usize>, Vec<GroupMapper>) {
 
     let gm = groups.map(parse).collect();
 
-    (seeds, gm)
+    Almanac::new(seeds, gm).unwrap()
 }
 
 fn solve(seeds: impl IntoIterator<Item = usize>, mappers: &[GroupMapper]) -> usize {
@@ -105,37 +337,21 @@ fn solve(seeds: impl IntoIterator<Item = usize>, mappers: &[GroupMapper]) -> usi
 }
 
 #[aoc(day5, part1)]
-pub fn part1((seeds, mappers): &(Vec<usize>, Vec<GroupMapper>)) -> usize {
-    solve(seeds.iter().copied(), mappers)
+pub fn part1(almanac: &Almanac) -> usize {
+    solve(almanac.seeds.iter().copied(), &almanac.stages)
 }
 
 #[aoc(day5, part2)]
-pub fn part2((seeds_range, mappers): &(Vec<usize>, Vec<GroupMapper>)) -> usize {
-    let mut temp = vec![];
-    let mut output = vec![];
-    let mut input = vec![];
+pub fn part2(almanac: &Almanac) -> usize {
+    let map = IntervalMap::compose_all(&almanac.stages);
 
-    seeds_range
+    almanac
+        .seeds
         .chunks(2)
-        .map(|pair| {
-            input.push(pair[0]..(pair[0] + pair[1]));
-            for m in mappers {
-                m.apply_range(&mut input, &mut temp, &mut output);
-                std::mem::swap(&mut input, &mut output);
-            }
-
-            let min = input.iter().map(|x| x.start).min().unwrap();
-            input.clear();
-            min
-        })
+        .filter_map(|pair| map.min_image(pair[0]..(pair[0] + pair[1])))
+        .map(|(_, location)| location)
         .min()
         .unwrap()
-
-    // seeds_range
-    //     .par_chunks(2)
-    //     .map(|x| solve(x[0]..(x[0] + x[1]), mappers))
-    //     .min()
-    //     .unwrap()
 }
 
 #[cfg(test)]
@@ -206,6 +422,65 @@ humidity-to-location map:
         assert_eq!(part2(&generator(SAMPLE)), 46);
     }
 
+    #[test]
+    pub fn interval_map_test() {
+        let Almanac {
+            seeds,
+            stages: mappers,
+        } = generator(SAMPLE);
+        let map = IntervalMap::compose_all(&mappers);
+        let inverse = map.inverse().unwrap();
+
+        for seed in 0..200 {
+            assert_eq!(map.apply(seed), solve([seed], &mappers));
+            assert_eq!(inverse.apply(map.apply(seed)), seed);
+        }
+        assert_eq!(
+            seeds.iter().map(|&s| map.apply(s)).collect::<Vec<_>>(),
+            [82, 43, 86, 35]
+        );
+        assert_eq!(map.min_image(79..93), Some((82, 46)));
+        assert_eq!(inverse.apply(46), 82);
+        assert_eq!(map.compose(&inverse), IntervalMap::default());
+        assert_eq!(map.breakpoints().next(), Some(0));
+    }
+
+    #[test]
+    pub fn route_test() {
+        let almanac = generator(SAMPLE);
+        assert_eq!(
+            almanac.categories().collect::<Vec<_>>(),
+            [
+                "seed",
+                "soil",
+                "fertilizer",
+                "water",
+                "light",
+                "temperature",
+                "humidity",
+                "location"
+            ]
+        );
+        assert_eq!(almanac.map_value("seed", "location", 79), Ok(82));
+        assert_eq!(almanac.map_value("soil", "humidity", 81), Ok(78));
+        assert_eq!(almanac.map_value("location", "seed", 82), Ok(79));
+        assert_eq!(
+            almanac.map_value("seed", "dirt", 79),
+            Err(RouteError::UnknownCategory("dirt".to_string()))
+        );
+
+        let mut groups = SAMPLE.split("\n\n").skip(1).map(parse).collect_vec();
+        groups.reverse();
+        let shuffled = Almanac::new(vec![], groups.clone()).unwrap();
+        assert_eq!(shuffled.map_value("seed", "location", 79), Ok(82));
+
+        groups.push(groups[0].clone());
+        assert_eq!(
+            Almanac::new(vec![], groups).unwrap_err(),
+            RouteError::DuplicateSource("humidity".to_string())
+        );
+    }
+
     mod regression {
         use super::*;
 
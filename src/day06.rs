@@ -1,81 +1,157 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use nom::{
     IResult, Parser,
-    bytes::complete::{tag, take_until, take_while1},
-    character::complete::{space0, space1},
+    bytes::complete::{tag, take_until},
+    character::complete::{digit1, space0, space1},
     multi::separated_list1,
 };
-use num::integer::sqrt;
+use std::str::FromStr;
 
-use crate::common::nom::{nom_lines, nom_usize, process_input};
+use num::{CheckedMul, Integer};
 
-fn parse_line(s: &str) -> IResult<&str, Vec<usize>> {
+use crate::common::nom::{nom_lines, process_input};
+
+fn parse_line(s: &str) -> IResult<&str, Vec<&str>> {
     let (s, _) = take_until(":")(s)?;
     let (s, _) = tag(":")(s)?;
     let (s, _) = space0(s)?;
 
-    separated_list1(space1, nom_usize).parse(s)
+    separated_list1(space1, digit1).parse(s)
 }
 
-fn parse_number(s: &str) -> IResult<&str, usize> {
-    let (s, _) = take_until(":")(s)?;
-    let (s, _) = tag(":")(s)?;
-    let (s, _) = space1(s)?;
-    let (s, line) = take_while1(|x: char| x != '\n')(s)?;
-
-    Ok((
-        s,
-        line.bytes()
-            .map(|c| {
-                if c.is_ascii_digit() {
-                    (c - b'0', 10)
-                } else {
-                    (0, 1)
-                }
-            })
-            .fold(0, |total, (n, multi)| total * multi + usize::from(n)),
-    ))
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Kerning {
+    None,
+    All,
+    Groups(usize),
 }
 
-#[aoc_generator(day6, part1)]
-pub fn generator_p1(input: &str) -> Vec<Vec<usize>> {
-    process_input(nom_lines(parse_line))(input)
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Races {
+    times: Vec<String>,
+    distances: Vec<String>,
 }
 
-#[aoc_generator(day6, part2)]
-pub fn generator_p2(input: &str) -> Vec<usize> {
-    process_input(nom_lines(parse_number))(input)
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RaceError {
+    ColumnCount { times: usize, distances: usize },
+    NoRaces,
+    // a kerned group that doesn't fit in the requested type
+    Parse(String),
 }
 
-// dist = (x - time) * x
-// 0 = x² - time*x - dist
-fn ways(time: usize, dist: usize) -> usize {
-    let pyth = sqrt(time * time - 4 * dist);
-    let r0 = (time - pyth) / 2;
-    let r1 = (time + pyth) / 2;
+impl Races {
+    fn kern<T: FromStr>(columns: &[String], kerning: Kerning) -> Result<Vec<T>, RaceError> {
+        let size = match kerning {
+            Kerning::None => 1,
+            Kerning::All => columns.len().max(1),
+            Kerning::Groups(n) => n.max(1),
+        };
+
+        columns
+            .chunks(size)
+            .map(|group| {
+                let group = group.concat();
+                group.parse().map_err(|_| RaceError::Parse(group))
+            })
+            .collect()
+    }
+
+    pub fn races<T: FromStr>(&self, kerning: Kerning) -> Result<Vec<(T, T)>, RaceError> {
+        if self.times.len() != self.distances.len() {
+            return Err(RaceError::ColumnCount {
+                times: self.times.len(),
+                distances: self.distances.len(),
+            });
+        }
+
+        Ok(Self::kern(&self.times, kerning)?
+            .into_iter()
+            .zip(Self::kern(&self.distances, kerning)?)
+            .collect())
+    }
 
-    let r0 = r0 + usize::from(r0 * (time - r0) <= dist);
-    let r1 = r1 - usize::from(r1 * (time - r1) <= dist);
+    pub fn columns<T: FromStr>(&self) -> Result<Vec<(T, T)>, RaceError> {
+        self.races(Kerning::None)
+    }
 
-    r1 - r0 + 1
+    pub fn kerned<T: FromStr>(&self) -> Result<(T, T), RaceError> {
+        self.races(Kerning::All)?
+            .into_iter()
+            .next()
+            .ok_or(RaceError::NoRaces)
+    }
 }
 
-#[aoc(day6, part1)]
-pub fn part1(inputs: &[Vec<usize>]) -> usize {
-    inputs[0]
+#[aoc_generator(day6)]
+pub fn generator(input: &str) -> Races {
+    let lines = process_input(nom_lines(parse_line))(input);
+    let [times, distances] =
+        [&lines[0], &lines[1]].map(|line| line.iter().map(|col| col.to_string()).collect());
+
+    Races { times, distances }
+}
+
+// hold * (time - hold) peaks at time / 2 and is symmetric about it, so the
+// winning holds are the first hold that beats `dist` up to its mirror image.
+//
+// Returns the inclusive range of hold times that beat `dist`, if any.
+pub fn winning_holds<T>(time: &T, dist: &T) -> Option<(T, T)>
+where
+    T: Integer + CheckedMul + Clone + From<u8>,
+{
+    // a product too big for T certainly beats `dist`
+    let beats = |hold: &T| {
+        hold.checked_mul(&(time.clone() - hold.clone()))
+            .is_none_or(|d| d > *dist)
+    };
+
+    let mut lo = T::zero();
+    let mut hi = time.clone() / T::from(2);
+    if !beats(&hi) {
+        return None;
+    }
+
+    while lo < hi {
+        let mid = lo.clone() + (hi.clone() - lo.clone()) / T::from(2);
+        if beats(&mid) {
+            hi = mid;
+        } else {
+            lo = mid + T::one();
+        }
+    }
+
+    Some((lo.clone(), time.clone() - lo))
+}
+
+pub fn ways<T>(time: &T, dist: &T) -> T
+where
+    T: Integer + CheckedMul + Clone + From<u8>,
+{
+    winning_holds(time, dist).map_or_else(T::zero, |(r0, r1)| r1 - r0 + T::one())
+}
+
+fn product(races: &[(u128, u128)]) -> usize {
+    races
         .iter()
-        .zip(&inputs[1])
-        .map(|(x, y)| ways(*x, *y))
+        .map(|(time, dist)| usize::try_from(ways(time, dist)).unwrap())
         .product()
 }
 
+#[aoc(day6, part1)]
+pub fn part1(races: &Races) -> usize {
+    product(&races.columns().unwrap())
+}
+
 #[aoc(day6, part2)]
-pub fn part2(inputs: &[usize]) -> usize {
-    ways(inputs[0], inputs[1])
+pub fn part2(races: &Races) -> usize {
+    product(&[races.kerned().unwrap()])
 }
 
 #[cfg(test)]
 mod tests {
+    use num::BigUint;
+
     use super::*;
 
     const SAMPLE: &str = r"Time:      7  15   30
@@ -83,19 +159,78 @@ Distance:  9  40  200";
 
     #[test]
     pub fn input_test() {
-        println!("{:?}", generator_p1(SAMPLE));
+        println!("{:?}", generator(SAMPLE));
 
         // assert_eq!(generator(SAMPLE), Object());
     }
 
     #[test]
     pub fn part1_test() {
-        assert_eq!(part1(&generator_p1(SAMPLE)), 288);
+        assert_eq!(part1(&generator(SAMPLE)), 288);
     }
 
     #[test]
     pub fn part2_test() {
-        assert_eq!(part2(&generator_p2(SAMPLE)), 71503);
+        assert_eq!(part2(&generator(SAMPLE)), 71503);
+    }
+
+    #[test]
+    pub fn kerning_test() {
+        let races = generator(SAMPLE);
+        assert_eq!(races.columns(), Ok(vec![(7u8, 9u8), (15, 40), (30, 200)]));
+        assert_eq!(races.kerned(), Ok((71530u128, 940200u128)));
+        assert_eq!(
+            races.races(Kerning::Groups(2)),
+            Ok(vec![(715u128, 940u128), (30, 200)])
+        );
+        assert_eq!(product(&races.races(Kerning::Groups(2)).unwrap()), 712 * 9);
+        assert_eq!(
+            races.kerned::<u8>(),
+            Err(RaceError::Parse("71530".to_string()))
+        );
+
+        let long = generator(&format!(
+            "Time: {}\nDistance: {}",
+            ["9999999999"; 5].join(" "),
+            ["1"; 5].join(" ")
+        ));
+        assert!(long.kerned::<u128>().is_err());
+        let (time, dist) = long.kerned::<BigUint>().unwrap();
+        assert_eq!(ways(&time, &dist), time - 1u8);
+
+        assert_eq!(
+            generator("Time: 7 15\nDistance: 9").columns::<u128>(),
+            Err(RaceError::ColumnCount {
+                times: 2,
+                distances: 1
+            })
+        );
+    }
+
+    #[test]
+    pub fn winning_holds_test() {
+        assert_eq!(winning_holds(&7u128, &9), Some((2, 5)));
+        assert_eq!(winning_holds(&30u128, &200), Some((11, 19)));
+        assert_eq!(winning_holds(&4u128, &3), Some((2, 2)));
+        assert_eq!(winning_holds(&4u128, &4), None);
+        assert_eq!(winning_holds(&0u128, &0), None);
+        assert_eq!(winning_holds(&1u128, &0), None);
+        assert_eq!(winning_holds(&5u128, &100), None);
+
+        let time = BigUint::from(10u8).pow(30);
+        let dist = BigUint::from(0u8);
+        assert_eq!(
+            winning_holds(&time, &dist),
+            Some((BigUint::from(1u8), time.clone() - 1u8))
+        );
+        assert_eq!(ways(&time, &dist), time - 1u8);
+
+        let time = 10u128.pow(20);
+        assert_eq!(winning_holds(&time, &1), Some((1, time - 1)));
+        assert_eq!(
+            winning_holds(&u128::MAX, &u128::MAX),
+            Some((2, u128::MAX - 2))
+        );
     }
 
     mod regression {
@@ -107,11 +242,10 @@ Distance:  9  40  200";
         #[test]
         pub fn test() {
             let input = INPUT.trim_end_matches('\n');
-            let output1 = generator_p1(input);
-            let output2 = generator_p2(input);
+            let output = generator(input);
 
-            assert_eq!(part1(&output1), ANSWERS.0);
-            assert_eq!(part2(&output2), ANSWERS.1);
+            assert_eq!(part1(&output), ANSWERS.0);
+            assert_eq!(part2(&output), ANSWERS.1);
         }
     }
 }
@@ -1,20 +1,14 @@
-use std::cmp::{Ordering, Reverse};
+use std::cmp::Reverse;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 use nom::{
-    IResult, Parser,
-    character::complete::{one_of, space1},
-    combinator::map,
+    IResult,
+    character::complete::{alphanumeric1, space1},
 };
 
-use crate::common::{
-    heap_retain,
-    nom::{nom_lines, nom_usize, process_input},
-};
-
-type HandValue = Reverse<u8>;
+use crate::common::nom::{nom_lines, nom_usize, process_input};
 
-#[derive(Debug, PartialEq, PartialOrd, Ord, Eq, Clone)]
+#[derive(Debug, PartialEq, PartialOrd, Ord, Eq, Clone, Copy)]
 pub enum HandTypes {
     Five,
     Four,
@@ -27,108 +21,191 @@ pub enum HandTypes {
 
 #[derive(Debug, Clone)]
 pub struct Hand {
-    cards: [HandValue; 5],
+    cards: String,
     value: usize,
-    part1: HandTypes,
-    part2: HandTypes,
 }
 
-impl Hand {
-    fn classify(cards: &[HandValue; 5]) -> (HandTypes, HandTypes) {
-        let helper = |count, sec| match count {
-            5 => HandTypes::Five,
-            4 => HandTypes::Four,
-            3 if sec == 2 => HandTypes::Full,
-            3 => HandTypes::Three,
-            2 if sec == 2 => HandTypes::TwoPair,
-            2 => HandTypes::OnePair,
-            1 => HandTypes::High,
-            _ => panic!("unknown hand type {:?}", cards),
-        };
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Rules {
+    // weakest to strongest
+    pub ranks: Vec<char>,
+    // wildcard and the strength it has when breaking ties
+    pub wildcards: Vec<(char, u8)>,
+    pub hand_size: usize,
+    // strongest to weakest; a hand matches the first entry whose counts it
+    // can cover with its own sorted card counts
+    pub hand_types: Vec<(Vec<usize>, HandTypes)>,
+}
 
-        let mut counts = [0usize; 15];
-        for &Reverse(c) in cards {
-            if c == 11 {
-                counts[1] += 1;
-            } else {
-                counts[usize::from(c)] += 1;
-            }
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Evaluation {
+    pub hand_type: HandTypes,
+    // what each wildcard stands in for, in the order they appear in the hand
+    pub substitute: Vec<char>,
+    strength: usize,
+    tiebreak: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Explanation {
+    pub cards: String,
+    pub hand_type: HandTypes,
+    pub substitute: Vec<char>,
+    pub rank: usize,
+    pub winnings: usize,
+}
+
+impl Evaluation {
+    fn key(&self) -> (Reverse<usize>, &[u8]) {
+        (Reverse(self.strength), &self.tiebreak)
+    }
+}
+
+impl Rules {
+    pub fn standard() -> Self {
+        Self {
+            ranks: "23456789TJQKA".chars().collect(),
+            wildcards: vec![],
+            hand_size: 5,
+            hand_types: vec![
+                (vec![5], HandTypes::Five),
+                (vec![4], HandTypes::Four),
+                (vec![3, 2], HandTypes::Full),
+                (vec![3], HandTypes::Three),
+                (vec![2, 2], HandTypes::TwoPair),
+                (vec![2], HandTypes::OnePair),
+                (vec![1], HandTypes::High),
+            ],
+        }
+    }
+
+    pub fn jokers() -> Self {
+        Self {
+            wildcards: vec![('J', 0)],
+            ..Self::standard()
         }
+    }
 
-        let jokers = counts[1];
-        let [max, sec] = counts[2..]
+    fn wildcard(&self, card: char) -> Option<u8> {
+        self.wildcards
             .iter()
-            .fold([0, 0], |acc, &n| heap_retain::accumulate_max_n(acc, n));
+            .find_map(|&(wild, strength)| (wild == card).then_some(strength))
+    }
 
-        let part2 = helper(max + jokers, sec);
-        let [max, sec] = heap_retain::accumulate_max_n([max, sec], jokers);
-        let part1 = helper(max, sec);
+    fn classify(&self, mut counts: Vec<usize>) -> Option<usize> {
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        self.hand_types.iter().position(|(pattern, _)| {
+            pattern.len() <= counts.len() && pattern.iter().zip(&counts).all(|(p, c)| c >= p)
+        })
+    }
+
+    // The strongest hand type the wildcards can complete. Pairing the pattern's
+    // largest groups with the most common ranks needs the fewest wildcards, and
+    // any left over join the largest group. Ties go to the stronger card.
+    fn substitute(&self, counts: &[usize], wilds: usize) -> Option<(usize, Vec<char>)> {
+        let mut ranks: Vec<usize> = (0..self.ranks.len())
+            .filter(|&rank| self.wildcard(self.ranks[rank]).is_none())
+            .collect();
+        ranks.sort_unstable_by_key(|&rank| Reverse((counts[rank], rank)));
 
-        (part1, part2)
+        self.hand_types
+            .iter()
+            .enumerate()
+            .find_map(|(strength, (pattern, _))| {
+                if pattern.is_empty() || pattern.len() > ranks.len() {
+                    return None;
+                }
+
+                let mut substitute = Vec::with_capacity(wilds);
+                for (&p, &rank) in pattern.iter().zip(&ranks) {
+                    let needed = p.saturating_sub(counts[rank]);
+                    substitute.extend(std::iter::repeat_n(self.ranks[rank], needed));
+                }
+                if substitute.len() > wilds {
+                    return None;
+                }
+
+                let leftover = wilds - substitute.len();
+                substitute.extend(std::iter::repeat_n(self.ranks[ranks[0]], leftover));
+                Some((strength, substitute))
+            })
     }
 
-    fn cards_compare<const JOKER: bool>(s: &[HandValue], other: &[HandValue]) -> Ordering {
-        for (a, b) in s.iter().zip(other.iter()) {
-            let a = if JOKER && a.0 == 11 { Reverse(1) } else { *a };
-            let b = if JOKER && b.0 == 11 { Reverse(1) } else { *b };
+    pub fn evaluate(&self, cards: &str) -> Option<Evaluation> {
+        if cards.chars().count() != self.hand_size {
+            return None;
+        }
 
-            let c = a.cmp(&b);
-            if c != Ordering::Equal {
-                return c;
+        let mut counts = vec![0; self.ranks.len()];
+        let mut wilds = 0;
+        let mut tiebreak = Vec::with_capacity(self.hand_size);
+        for card in cards.chars() {
+            if let Some(strength) = self.wildcard(card) {
+                wilds += 1;
+                tiebreak.push(strength);
+            } else {
+                let rank = self.ranks.iter().position(|&r| r == card)?;
+                counts[rank] += 1;
+                tiebreak.push(u8::try_from(rank + 1).unwrap());
             }
         }
-        Ordering::Equal
-    }
 
-    fn compare<const JOKER: bool>(&self, other: &Self) -> Ordering {
-        let (o, s) = if JOKER {
-            (&other.part2, &self.part2)
+        let (strength, substitute) = if wilds == 0 {
+            (self.classify(counts)?, vec![])
         } else {
-            (&other.part1, &self.part1)
+            self.substitute(&counts, wilds)?
         };
 
-        o.cmp(s)
-            .then_with(|| Self::cards_compare::<JOKER>(&other.cards, &self.cards))
+        Some(Evaluation {
+            hand_type: self.hand_types[strength].1,
+            substitute,
+            strength,
+            tiebreak,
+        })
     }
-}
 
-fn parse_card(s: &str) -> IResult<&str, HandValue> {
-    map(one_of("23456789TJQKA"), |c: char| {
-        Reverse(match c {
-            '2'..='9' => c as u8 - b'0',
-            'T' => 10,
-            'J' => 11,
-            'Q' => 12,
-            'K' => 13,
-            'A' => 14,
-            _ => panic!("unknown symbol"),
-        })
-    })
-    .parse(s)
-}
+    // Hands paired with their evaluation, weakest first.
+    fn ranked<'a>(&self, hands: &'a [Hand]) -> Option<Vec<(&'a Hand, Evaluation)>> {
+        let mut ranked = hands
+            .iter()
+            .map(|hand| Some((hand, self.evaluate(&hand.cards)?)))
+            .collect::<Option<Vec<_>>>()?;
+        ranked.sort_by(|(_, a), (_, b)| a.key().cmp(&b.key()));
 
-fn parse_hand(s: &str) -> IResult<&str, [HandValue; 5]> {
-    map(
-        (parse_card, parse_card, parse_card, parse_card, parse_card),
-        |x| [x.0, x.1, x.2, x.3, x.4],
-    )
-    .parse(s)
+        Some(ranked)
+    }
+
+    pub fn explain(&self, hands: &[Hand]) -> Option<Vec<Explanation>> {
+        Some(
+            self.ranked(hands)?
+                .into_iter()
+                .enumerate()
+                .map(|(place, (hand, eval))| Explanation {
+                    cards: hand.cards.clone(),
+                    hand_type: eval.hand_type,
+                    substitute: eval.substitute,
+                    rank: place + 1,
+                    winnings: hand.value * (place + 1),
+                })
+                .collect(),
+        )
+    }
+
+    pub fn score(&self, hands: &[Hand]) -> Option<usize> {
+        Some(self.explain(hands)?.iter().map(|row| row.winnings).sum())
+    }
 }
 
 fn parse_line(s: &str) -> IResult<&str, Hand> {
-    let (s, cards) = parse_hand(s)?;
+    let (s, cards) = alphanumeric1(s)?;
     let (s, _) = space1(s)?;
     let (s, value) = nom_usize(s)?;
 
-    let (part1, part2) = Hand::classify(&cards);
-
     Ok((
         s,
         Hand {
-            cards,
+            cards: cards.to_string(),
             value,
-            part1,
-            part2,
         },
     ))
 }
@@ -138,26 +215,14 @@ fn generator(input: &str) -> Vec<Hand> {
     process_input(nom_lines(parse_line))(input)
 }
 
-fn solve<const JOKER: bool>(inputs: &[Hand]) -> usize {
-    let mut inputs = inputs.to_vec();
-    inputs.sort_unstable_by(|a, b| a.compare::<JOKER>(b));
-
-    inputs
-        .into_iter()
-        .enumerate()
-        // .inspect(|x| println!("{x:?}"))
-        .map(|(place, hand)| hand.value * (place + 1))
-        .sum()
-}
-
 #[aoc(day7, part1)]
 pub fn part1(inputs: &[Hand]) -> usize {
-    solve::<false>(inputs)
+    Rules::standard().score(inputs).unwrap()
 }
 
 #[aoc(day7, part2)]
 pub fn part2(inputs: &[Hand]) -> usize {
-    solve::<true>(inputs)
+    Rules::jokers().score(inputs).unwrap()
 }
 
 #[cfg(test)]
@@ -187,6 +252,74 @@ QQQJA 483";
         assert_eq!(part2(&generator(SAMPLE)), 5905);
     }
 
+    #[test]
+    pub fn explain_test() {
+        let table = Rules::jokers().explain(&generator(SAMPLE)).unwrap();
+        assert_eq!(
+            table[4],
+            Explanation {
+                cards: "KTJJT".to_string(),
+                hand_type: HandTypes::Four,
+                substitute: vec!['T', 'T'],
+                rank: 5,
+                winnings: 1100,
+            }
+        );
+        assert_eq!(
+            table
+                .iter()
+                .map(|row| (row.cards.as_str(), row.substitute.as_slice()))
+                .collect::<Vec<_>>(),
+            [
+                ("32T3K", &[][..]),
+                ("KK677", &[]),
+                ("T55J5", &['5']),
+                ("QQQJA", &['Q']),
+                ("KTJJT", &['T', 'T']),
+            ]
+        );
+        assert_eq!(table.iter().map(|row| row.winnings).sum::<usize>(), 5905);
+    }
+
+    #[test]
+    pub fn rules_test() {
+        let rules = Rules::jokers();
+        let eval = rules.evaluate("KTJJT").unwrap();
+        assert_eq!(eval.hand_type, HandTypes::Four);
+        assert_eq!(eval.substitute, ['T', 'T']);
+        let eval = rules.evaluate("JJJJJ").unwrap();
+        assert_eq!(eval.hand_type, HandTypes::Five);
+        assert_eq!(eval.substitute, ['A'; 5]);
+        assert_eq!(rules.evaluate("KTJJ"), None);
+        assert_eq!(rules.evaluate("KTJJX"), None);
+
+        let rules = Rules {
+            ranks: "6789TJQKA".chars().collect(),
+            wildcards: vec![('2', 0), ('3', 0)],
+            hand_size: 3,
+            hand_types: vec![
+                (vec![3], HandTypes::Three),
+                (vec![2], HandTypes::OnePair),
+                (vec![1], HandTypes::High),
+            ],
+        };
+        let hands = generator("A3K 10\n7Q6 20\nKK9 30");
+        assert_eq!(rules.evaluate("A3K").unwrap().substitute, ['A']);
+        assert_eq!(rules.score(&hands), Some(20 + 30 * 2 + 10 * 3));
+
+        // two pair beats three of a kind, so the jokers should split
+        let mut rules = Rules::jokers();
+        rules.hand_types.swap(3, 4);
+        let eval = rules.evaluate("AKJJQ").unwrap();
+        assert_eq!(eval.hand_type, HandTypes::TwoPair);
+        assert_eq!(eval.substitute, ['A', 'K']);
+        assert_eq!(
+            rules.evaluate("AAJ23").unwrap().hand_type,
+            HandTypes::TwoPair
+        );
+        assert_eq!(rules.evaluate("AAJJ2").unwrap().hand_type, HandTypes::Four);
+    }
+
     mod regression {
         use super::*;
 
@@ -1,37 +1,95 @@
-use ahash::HashMapExt;
 use aoc_runner_derive::{aoc, aoc_generator};
 use nom::{
     IResult, Parser,
-    bytes::complete::{tag, take},
-    character::complete::newline,
-    combinator::map,
+    bytes::complete::tag,
+    character::complete::{alphanumeric1, newline},
     sequence::delimited,
 };
-use num::Integer;
-use rayon::iter::{ParallelBridge, ParallelIterator};
+use num::{Integer, integer::ExtendedGcd};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use rustc_hash::FxHashMap as HashMap;
-use smallstr::SmallString;
 
 use crate::common::nom::fold_separated_list0;
 
-type String = SmallString<[u8; 3]>;
-
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Direction {
     Left,
     Right,
 }
 
-fn parse_node(s: &str) -> IResult<&str, (String, String, String)> {
-    let (s, key) = map(take(3usize), String::from).parse(s)?;
+const UNDEFINED: u32 = u32::MAX;
+
+#[derive(Debug, Default, Clone)]
+pub struct Network {
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+    next: Vec<[u32; 2]>,
+}
+
+impl Network {
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+
+        let id = u32::try_from(self.names.len()).unwrap();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.next.push([UNDEFINED; 2]);
+        id
+    }
+
+    fn add(&mut self, key: &str, l: &str, r: &str) {
+        let key = self.intern(key);
+        let l = self.intern(l);
+        let r = self.intern(r);
+        self.next[key as usize] = [l, r];
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: u32) -> &str {
+        &self.names[id as usize]
+    }
+
+    pub fn successors(&self, id: u32) -> Option<[u32; 2]> {
+        let next = self.next[id as usize];
+        (next != [UNDEFINED; 2]).then_some(next)
+    }
+
+    pub fn step(&self, id: u32, dir: Direction) -> Option<u32> {
+        self.successors(id).map(|[l, r]| match dir {
+            Direction::Left => l,
+            Direction::Right => r,
+        })
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = (&str, &str, Direction)> + '_ {
+        (0..self.len() as u32)
+            .filter_map(|id| Some((id, self.successors(id)?)))
+            .flat_map(move |(id, [l, r])| {
+                [(l, Direction::Left), (r, Direction::Right)]
+                    .map(|(to, dir)| (self.name(id), self.name(to), dir))
+            })
+    }
+}
+
+fn parse_node(s: &str) -> IResult<&str, (&str, &str, &str)> {
+    let (s, key) = alphanumeric1(s)?;
     let (s, _) = tag(" = ")(s)?;
     let (s, (l, _, r)) = delimited(
         tag("("),
-        (
-            map(take(3usize), String::from),
-            tag(", "),
-            map(take(3usize), String::from),
-        ),
+        (alphanumeric1, tag(", "), alphanumeric1),
         tag(")"),
     )
     .parse(s)?;
@@ -40,7 +98,7 @@ fn parse_node(s: &str) -> IResult<&str, (String, String, String)> {
 }
 
 #[aoc_generator(day8)]
-pub fn generator(input: &str) -> (Vec<Direction>, HashMap<String, (String, String)>) {
+pub fn generator(input: &str) -> (Vec<Direction>, Network) {
     let (instructions, network) = input.split_once("\n\n").unwrap();
 
     let ins = instructions
@@ -54,52 +112,239 @@ pub fn generator(input: &str) -> (Vec<Direction>, HashMap<String, (String, Strin
         })
         .collect();
 
-    let (_, hm) = fold_separated_list0(newline, parse_node, HashMap::new, |mut hm, (key, l, r)| {
-        hm.insert(key, (l, r));
-        hm
-    })
+    let (_, net) = fold_separated_list0(
+        newline,
+        parse_node,
+        Network::default,
+        |mut net, (key, l, r)| {
+            net.add(key, l, r);
+            net
+        },
+    )
     .parse(network)
     .unwrap();
-    (ins, hm)
+    (ins, net)
 }
 
-#[aoc(day8, part1)]
-pub fn part1((ins, net): &(Vec<Direction>, HashMap<String, (String, String)>)) -> usize {
-    solve("AAA", ins, net, false)
+#[derive(Debug, PartialEq, Eq)]
+pub enum WalkError {
+    UnknownNode(String),
+    NoInstructions,
+    Unsolvable,
+    SearchLimit,
+    // the first meeting is past usize::MAX
+    Overflow,
 }
 
-fn solve<'a>(
-    mut location: &'a str,
-    ins: &'a [Direction],
-    net: &'a HashMap<String, (String, String)>,
-    part2: bool,
-) -> usize {
-    let mut steps = 0;
-
-    for ins in ins.iter().cycle() {
-        if location == "ZZZ" || part2 && location.ends_with('Z') {
-            break;
+// Combining residue sets can grow multiplicatively; past this we search instead.
+const RESIDUE_LIMIT: usize = 1 << 16;
+const SEARCH_LIMIT: usize = 1 << 24;
+
+// A single walker's path through (node, instruction index) states: it spends
+// `tail` steps before entering a loop of `cycle` steps. `tail_hits` are the
+// steps before the loop that land on an end node, `cycle_hits` the ones within
+// the first pass of the loop.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Ghost {
+    pub tail: usize,
+    pub cycle: usize,
+    pub tail_hits: Vec<usize>,
+    pub cycle_hits: Vec<usize>,
+}
+
+impl Ghost {
+    fn new(
+        start: u32,
+        ins: &[Direction],
+        net: &Network,
+        is_end: impl Fn(&str) -> bool,
+    ) -> Result<Self, WalkError> {
+        if ins.is_empty() {
+            return Err(WalkError::NoInstructions);
         }
 
-        steps += 1;
-        let new_loc = match ins {
-            Direction::Left => net.get(location).unwrap().0.as_str(),
-            Direction::Right => net.get(location).unwrap().1.as_str(),
-        };
+        let ends = net
+            .names
+            .iter()
+            .map(|name| is_end(name))
+            .collect::<Vec<_>>();
+        let mut seen = vec![usize::MAX; net.len() * ins.len()];
+        let mut hits = vec![];
+        let mut location = start;
 
-        location = new_loc;
+        for step in 0.. {
+            let idx = step % ins.len();
+            let state = location as usize * ins.len() + idx;
+            if seen[state] != usize::MAX {
+                let first = seen[state];
+                let (tail_hits, cycle_hits) = hits.iter().partition(|&&hit| hit < first);
+                return Ok(Self {
+                    tail: first,
+                    cycle: step - first,
+                    tail_hits,
+                    cycle_hits,
+                });
+            }
+            seen[state] = step;
+
+            if ends[location as usize] {
+                hits.push(step);
+            }
+
+            location = net
+                .step(location, ins[idx])
+                .ok_or_else(|| WalkError::UnknownNode(net.name(location).to_string()))?;
+        }
+
+        unreachable!()
     }
 
-    steps
+    pub fn is_end_at(&self, step: usize) -> bool {
+        if step < self.tail {
+            self.tail_hits.binary_search(&step).is_ok()
+        } else {
+            let step = self.tail + (step - self.tail) % self.cycle;
+            self.cycle_hits.binary_search(&step).is_ok()
+        }
+    }
+}
+
+// Generalised CRT: combines x ≡ a (mod m) and x ≡ b (mod n) for any m and n.
+fn crt(a: i128, m: i128, b: i128, n: i128) -> Option<(i128, i128)> {
+    let ExtendedGcd { gcd, x, .. } = m.extended_gcd(&n);
+    if (b - a) % gcd != 0 {
+        return None;
+    }
+
+    let lcm = (m / gcd).checked_mul(n)?;
+    let k = ((b - a) / gcd % (n / gcd)).checked_mul(x)? % (n / gcd);
+    Some(((a + m.checked_mul(k)?).rem_euclid(lcm), lcm))
+}
+
+// Returns the first step at which every ghost is on an end node.
+pub fn first_meeting(ghosts: &[Ghost]) -> Result<usize, WalkError> {
+    let tail = ghosts.iter().map(|g| g.tail).max().unwrap_or(0);
+    if let Some(step) = (0..tail).find(|&step| ghosts.iter().all(|g| g.is_end_at(step))) {
+        return Ok(step);
+    }
+
+    // every step before `tail` has already been checked
+    if ghosts.iter().any(|g| g.cycle_hits.is_empty()) {
+        return Err(WalkError::Unsolvable);
+    }
+
+    let mut residues = vec![0i128];
+    let mut modulus = 1i128;
+    for (i, ghost) in ghosts.iter().enumerate() {
+        let cycle = ghost.cycle as i128;
+        let lcm = (modulus / modulus.gcd(&cycle)).checked_mul(cycle);
+        if lcm.is_none() || residues.len() * ghost.cycle_hits.len() > RESIDUE_LIMIT {
+            return search(&residues, modulus, tail, &ghosts[i..]);
+        }
+
+        residues = residues
+            .iter()
+            .flat_map(|&a| {
+                ghost
+                    .cycle_hits
+                    .iter()
+                    .filter_map(move |&hit| crt(a, modulus, hit as i128 % cycle, cycle))
+            })
+            .map(|(x, _)| x)
+            .collect();
+        residues.sort_unstable();
+        residues.dedup();
+        modulus = lcm.unwrap();
+
+        if residues.is_empty() {
+            return Err(WalkError::Unsolvable);
+        }
+    }
+
+    // a residue that overflows when lifted past `tail` is beaten by any that doesn't
+    let modulus = usize::try_from(modulus).map_err(|_| WalkError::Overflow)?;
+    let mut overflow = false;
+    residues
+        .into_iter()
+        .filter_map(|x| {
+            let x = usize::try_from(x).ok()?;
+            let step = if x >= tail {
+                Some(x)
+            } else {
+                (tail - x)
+                    .div_ceil(modulus)
+                    .checked_mul(modulus)
+                    .and_then(|offset| offset.checked_add(x))
+            };
+            overflow |= step.is_none();
+            step
+        })
+        .min()
+        .ok_or(if overflow {
+            WalkError::Overflow
+        } else {
+            WalkError::Unsolvable
+        })
+}
+
+// Walks the candidate steps already satisfied by the merged ghosts, checking the
+// rest directly.
+fn search(
+    residues: &[i128],
+    modulus: i128,
+    tail: usize,
+    rest: &[Ghost],
+) -> Result<usize, WalkError> {
+    let modulus = usize::try_from(modulus).map_err(|_| WalkError::Overflow)?;
+    let start = tail - tail % modulus;
+
+    let mut checked = 0;
+    for k in 0usize.. {
+        let base = k
+            .checked_mul(modulus)
+            .and_then(|offset| offset.checked_add(start))
+            .ok_or(WalkError::Overflow)?;
+
+        for &x in residues {
+            let step = usize::try_from(x)
+                .ok()
+                .and_then(|x| base.checked_add(x))
+                .ok_or(WalkError::Overflow)?;
+            if step < tail {
+                continue;
+            }
+
+            if rest.iter().all(|g| g.is_end_at(step)) {
+                return Ok(step);
+            }
+
+            checked += 1;
+            if checked == SEARCH_LIMIT {
+                return Err(WalkError::SearchLimit);
+            }
+        }
+    }
+
+    unreachable!()
+}
+
+#[aoc(day8, part1)]
+pub fn part1((ins, net): &(Vec<Direction>, Network)) -> usize {
+    let ghost = Ghost::new(net.id("AAA").unwrap(), ins, net, |loc| loc == "ZZZ").unwrap();
+    first_meeting(&[ghost]).unwrap()
+}
+
+pub fn ghosts(ins: &[Direction], net: &Network) -> Result<Vec<Ghost>, WalkError> {
+    (0..net.len() as u32)
+        .into_par_iter()
+        .filter(|&id| net.name(id).ends_with('A'))
+        .map(|id| Ghost::new(id, ins, net, |loc| loc.ends_with('Z')))
+        .collect()
 }
 
 #[aoc(day8, part2)]
-pub fn part2((ins, net): &(Vec<Direction>, HashMap<String, (String, String)>)) -> usize {
-    net.keys()
-        .par_bridge()
-        .filter(|k| k.ends_with('A'))
-        .map(|k| solve(k, ins, net, true))
-        .reduce(|| 1, |l, x| l.lcm(&x))
+pub fn part2((ins, net): &(Vec<Direction>, Network)) -> usize {
+    first_meeting(&ghosts(ins, net).unwrap()).unwrap()
 }
 
 #[cfg(test)]
@@ -150,6 +395,101 @@ XXX = (XXX, XXX)";
         assert_eq!(part2(&generator(SAMPLE3)), 6);
     }
 
+    #[test]
+    pub fn network_test() {
+        let (ins, net) = generator(
+            "LR
+
+START = (MIDDLE, START)
+MIDDLE = (START, FINISH)
+FINISH = (FINISH, FINISH)",
+        );
+        assert_eq!(net.len(), 3);
+        assert_eq!(net.id("MIDDLE"), Some(1));
+        assert_eq!(net.step(1, Direction::Right), net.id("FINISH"));
+        assert_eq!(
+            net.edges().take(2).collect::<Vec<_>>(),
+            [
+                ("START", "MIDDLE", Direction::Left),
+                ("START", "START", Direction::Right)
+            ]
+        );
+
+        let ghost = Ghost::new(0, &ins, &net, |loc| loc == "FINISH").unwrap();
+        assert_eq!(first_meeting(&[ghost]), Ok(2));
+
+        let (ins, net) = generator("\n\nAB = (AB, AB)");
+        assert_eq!(
+            Ghost::new(0, &ins, &net, |_| false),
+            Err(WalkError::NoInstructions)
+        );
+
+        let (ins, net) = generator("L\n\nAB = (CD, CD)");
+        assert_eq!(
+            Ghost::new(0, &ins, &net, |_| false),
+            Err(WalkError::UnknownNode("CD".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn ghost_test() {
+        let (ins, net) = generator(SAMPLE3);
+        let start = net.id("22A").unwrap();
+        let ghost = Ghost::new(start, &ins, &net, |loc| loc.ends_with('Z')).unwrap();
+        assert_eq!(
+            ghost,
+            Ghost {
+                tail: 1,
+                cycle: 6,
+                tail_hits: vec![],
+                cycle_hits: vec![3, 6],
+            }
+        );
+
+        // first hits are 1 and 2, but the walkers only line up at step 4
+        let (ins, net) = generator(
+            "L
+
+11A = (11Z, 11Z)
+11Z = (11B, 11B)
+11B = (11C, 11C)
+11C = (11Z, 11Z)
+22A = (22B, 22B)
+22B = (22Z, 22Z)
+22Z = (22B, 22B)",
+        );
+        assert_eq!(first_meeting(&ghosts(&ins, &net).unwrap()), Ok(4));
+
+        // the first meeting is 32589158477190044729, past usize::MAX
+        let primes = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+        let walkers: Vec<_> = primes
+            .iter()
+            .map(|&p| Ghost {
+                tail: 0,
+                cycle: p,
+                tail_hits: vec![],
+                cycle_hits: vec![p - 1],
+            })
+            .collect();
+        assert_eq!(first_meeting(&walkers), Err(WalkError::Overflow));
+
+        let (ins, net) = generator(
+            "L
+
+11A = (11Z, 11Z)
+11Z = (11Z, 11Z)
+22A = (22A, 22A)",
+        );
+        assert_eq!(
+            first_meeting(&ghosts(&ins, &net).unwrap()),
+            Err(WalkError::Unsolvable)
+        );
+        assert_eq!(
+            Ghost::new(0, &ins, &net, |loc| loc == "ZZZ").map(|g| first_meeting(&[g])),
+            Ok(Err(WalkError::Unsolvable))
+        );
+    }
+
     mod regression {
         use super::*;
 
@@ -1,61 +1,202 @@
+use std::str::FromStr;
+
 use aoc_runner_derive::{aoc, aoc_generator};
-use nom::{bytes::complete::tag, multi::separated_list0};
+use nom::{
+    IResult, Parser,
+    bytes::complete::tag,
+    character::complete::{char, digit1},
+    combinator::{map_res, opt, recognize},
+    multi::separated_list0,
+};
+use num::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, Integer, rational::Ratio};
 
-use crate::common::nom::{nom_i64, process_input};
+#[derive(Debug, PartialEq, Eq)]
+pub enum SequenceError {
+    Parse,
+    Overflow,
+    // the differences ran out before reaching a row of zeros
+    Undetermined,
+}
 
-#[aoc_generator(day9)]
-pub fn generator(input: &str) -> Vec<Vec<Vec<i64>>> {
-    let mut res = Vec::new();
-    for mut v in input
-        .lines()
-        .map(|line| process_input(separated_list0(tag(" "), nom_i64::<_, ()>))(line))
-    {
-        let mut differences = Vec::new();
-
-        while v.iter().any(|&x| x != 0) {
-            let v2 = diff(&v);
-            differences.push(v);
-            v = v2;
+#[derive(Debug, PartialEq, Eq)]
+pub struct LineError {
+    pub line: usize,
+    pub error: SequenceError,
+}
+
+pub trait Number:
+    Integer + Clone + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + FromStr + From<i64>
+{
+}
+
+impl<T> Number for T where
+    T: Integer + Clone + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + FromStr + From<i64>
+{
+}
+
+// The minimal-degree polynomial through `values`, stored as its Newton forward
+// differences: p(x) = sum of newton[k] * C(x, k).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Sequence<T = i128> {
+    values: Vec<T>,
+    newton: Vec<T>,
+}
+
+fn from_usize<T: Number>(n: usize) -> T {
+    T::from(i64::try_from(n).unwrap())
+}
+
+fn binomial<T: Number>(x: &T, k: usize) -> Option<T> {
+    (0..k).try_fold(T::one(), |acc, i| {
+        acc.checked_mul(&x.checked_sub(&from_usize(i))?)?
+            .checked_div(&from_usize(i + 1))
+    })
+}
+
+impl<T: Number> Sequence<T> {
+    pub fn new(values: Vec<T>) -> Result<Self, SequenceError> {
+        let mut newton = (0..values.len())
+            .map(|k| {
+                values[..=k]
+                    .iter()
+                    .enumerate()
+                    .try_fold(T::zero(), |acc, (j, y)| {
+                        let term = binomial(&from_usize::<T>(k), j)?.checked_mul(y)?;
+                        if (k - j) % 2 == 0 {
+                            acc.checked_add(&term)
+                        } else {
+                            acc.checked_sub(&term)
+                        }
+                    })
+                    .ok_or(SequenceError::Overflow)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if newton.len() > 1 && newton.last().is_some_and(|c| !c.is_zero()) {
+            return Err(SequenceError::Undetermined);
+        }
+
+        while newton.last().is_some_and(T::is_zero) {
+            newton.pop();
+        }
+
+        Ok(Self { values, newton })
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    // `None` for the zero polynomial
+    pub fn degree(&self) -> Option<usize> {
+        self.newton.len().checked_sub(1)
+    }
+
+    pub fn predict(&self, x: i64) -> Result<T, SequenceError> {
+        let x = T::from(x);
+        self.newton
+            .iter()
+            .enumerate()
+            .try_fold(T::zero(), |acc, (k, c)| {
+                acc.checked_add(&c.checked_mul(&binomial(&x, k)?)?)
+            })
+            .ok_or(SequenceError::Overflow)
+    }
+
+    // Coefficients of the polynomial in increasing powers of x.
+    pub fn coefficients(&self) -> Result<Vec<Ratio<T>>, SequenceError> {
+        let mut coefficients = vec![Ratio::from_integer(T::zero()); self.newton.len()];
+        // x(x-1)...(x-k+1) in increasing powers of x
+        let mut falling = vec![T::one()];
+        let mut factorial = T::one();
+
+        for (k, c) in self.newton.iter().enumerate() {
+            if k > 0 {
+                factorial = factorial
+                    .checked_mul(&from_usize(k))
+                    .ok_or(SequenceError::Overflow)?;
+            }
+
+            for (coefficient, f) in coefficients.iter_mut().zip(&falling) {
+                let numer = c.checked_mul(f).ok_or(SequenceError::Overflow)?;
+                *coefficient = coefficient
+                    .checked_add(&Ratio::new(numer, factorial.clone()))
+                    .ok_or(SequenceError::Overflow)?;
+            }
+
+            let mut next = vec![T::zero(); falling.len() + 1];
+            for (i, f) in falling.iter().enumerate() {
+                next[i + 1] = next[i + 1].checked_add(f).ok_or(SequenceError::Overflow)?;
+                next[i] = f
+                    .checked_mul(&from_usize(k))
+                    .and_then(|fk| next[i].checked_sub(&fk))
+                    .ok_or(SequenceError::Overflow)?;
+            }
+            falling = next;
         }
 
-        res.push(differences)
+        Ok(coefficients)
     }
+}
 
-    res
+fn nom_number<T: FromStr>(s: &str) -> IResult<&str, T> {
+    map_res(recognize((opt(char('-')), digit1)), str::parse).parse(s)
 }
 
-fn diff(v: &[i64]) -> Vec<i64> {
-    v.windows(2).map(|x| x[1] - x[0]).collect()
+pub fn parse_sequences<T: Number>(input: &str) -> Result<Vec<Sequence<T>>, LineError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let error = |error| LineError { line: i + 1, error };
+            let values = match separated_list0(tag(" "), nom_number).parse(line) {
+                Ok(("", values)) => values,
+                _ => return Err(error(SequenceError::Parse)),
+            };
+
+            Sequence::new(values).map_err(error)
+        })
+        .collect()
 }
 
-fn process<F>(differences: &[Vec<i64>], op: F) -> i64
-where
-    F: Fn(&Vec<i64>, i64) -> i64,
-{
-    differences
-        .iter()
-        .rev()
-        .fold(0, |constant, d| op(d, constant))
+#[aoc_generator(day9)]
+pub fn generator(input: &str) -> Vec<Sequence> {
+    parse_sequences(input).unwrap()
 }
 
-#[aoc(day9, part1)]
-pub fn part1(inputs: &[Vec<Vec<i64>>]) -> i64 {
+// Sums the predictions at `x`, reporting the line whose prediction or addition failed.
+pub fn solve<T: Number>(
+    inputs: &[Sequence<T>],
+    x: impl Fn(&Sequence<T>) -> i64,
+) -> Result<T, LineError> {
     inputs
         .iter()
-        .map(|x| process(x, |d, c| d.last().copied().unwrap() + c))
-        .sum()
+        .enumerate()
+        .try_fold(T::zero(), |acc, (i, seq)| {
+            let error = |error| LineError { line: i + 1, error };
+            let prediction = seq.predict(x(seq)).map_err(error)?;
+            acc.checked_add(&prediction)
+                .ok_or(error(SequenceError::Overflow))
+        })
+}
+
+#[aoc(day9, part1)]
+pub fn part1(inputs: &[Sequence]) -> i64 {
+    solve(inputs, |seq| seq.values().len() as i64)
+        .unwrap()
+        .try_into()
+        .unwrap()
 }
 
 #[aoc(day9, part2)]
-pub fn part2(inputs: &[Vec<Vec<i64>>]) -> i64 {
-    inputs
-        .iter()
-        .map(|x| process(x, |d, c| d.first().copied().unwrap() - c))
-        .sum()
+pub fn part2(inputs: &[Sequence]) -> i64 {
+    solve(inputs, |_| -1).unwrap().try_into().unwrap()
 }
 
 #[cfg(test)]
 mod tests {
+    use num::BigInt;
+
     use super::*;
 
     const SAMPLE: &str = r"0 3 6 9 12 15
@@ -79,6 +220,80 @@ mod tests {
         assert_eq!(part2(&generator(SAMPLE)), 2);
     }
 
+    #[test]
+    pub fn sequence_test() {
+        let seqs = generator(SAMPLE);
+        assert_eq!(
+            seqs.iter().map(Sequence::degree).collect::<Vec<_>>(),
+            [Some(1), Some(2), Some(3)]
+        );
+        assert_eq!(
+            seqs[1].coefficients(),
+            Ok(vec![Ratio::new(1, 1), Ratio::new(3, 2), Ratio::new(1, 2)])
+        );
+        assert_eq!(seqs[1].predict(-3), Ok(1));
+        assert_eq!(seqs[0].predict(1_000_000_000), Ok(3_000_000_000));
+        assert_eq!(seqs[2].predict(-2), Ok(-4));
+
+        assert_eq!(Sequence::new(vec![0i128, 0, 0]).unwrap().degree(), None);
+        assert_eq!(Sequence::new(vec![7i128]).unwrap().predict(10), Ok(7));
+        assert_eq!(
+            Sequence::new(vec![1i128, 2, 4]),
+            Err(SequenceError::Undetermined)
+        );
+    }
+
+    #[test]
+    pub fn overflow_test() {
+        let input = "0 3 6 9\n1 8 27 64 125 216\n0 1 2 3 4 x";
+        assert_eq!(
+            parse_sequences::<i64>(input).unwrap_err(),
+            LineError {
+                line: 3,
+                error: SequenceError::Parse
+            }
+        );
+
+        let input = "0 3 6 9\n1 8 27 64 125 216";
+        let seqs = parse_sequences::<i64>(input).unwrap();
+        assert_eq!(seqs[1].predict(3_000_000), Err(SequenceError::Overflow));
+        assert_eq!(
+            parse_sequences::<i128>(input).unwrap()[1].predict(3_000_000),
+            Ok(27_000_027_000_009_000_001)
+        );
+
+        assert_eq!(
+            solve(&seqs, |_| 3_000_000),
+            Err(LineError {
+                line: 2,
+                error: SequenceError::Overflow
+            })
+        );
+        let sums = parse_sequences::<i64>("9223372036854775807\n1").unwrap();
+        assert_eq!(
+            solve(&sums, |_| 0),
+            Err(LineError {
+                line: 2,
+                error: SequenceError::Overflow
+            })
+        );
+
+        let big = parse_sequences::<BigInt>(input).unwrap();
+        assert_eq!(
+            big[1].predict(i64::MAX),
+            Ok((BigInt::from(i64::MAX) + 1u8).pow(3))
+        );
+
+        let input = "1 2 3\n9223372036854775807 0 9223372036854775807";
+        assert_eq!(
+            parse_sequences::<i64>(input).unwrap_err(),
+            LineError {
+                line: 2,
+                error: SequenceError::Overflow
+            }
+        );
+    }
+
     mod regression {
         use super::*;
 
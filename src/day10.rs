@@ -1,10 +1,10 @@
 use ahash::HashSetExt;
 use aoc_runner_derive::{aoc, aoc_generator};
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use rustc_hash::FxHashSet as HashSet;
 
-#[derive(Debug)]
-enum Dir {
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dir {
     Down,
     Left,
     Up,
@@ -12,12 +12,24 @@ enum Dir {
 }
 
 impl Dir {
-    fn next_pos(&self, r: &mut usize, c: &mut usize) {
+    fn next_pos(&self, (r, c): (usize, usize), grid: &[Vec<u8>]) -> Option<(usize, usize)> {
+        let pos = match self {
+            Dir::Down => (r + 1, c),
+            Dir::Left => (r, c.checked_sub(1)?),
+            Dir::Up => (r.checked_sub(1)?, c),
+            Dir::Right => (r, c + 1),
+        };
+
+        grid.get(pos.0)?.get(pos.1)?;
+        Some(pos)
+    }
+
+    fn opposite(&self) -> Self {
         match self {
-            Dir::Down => *r += 1,
-            Dir::Left => *c -= 1,
-            Dir::Up => *r -= 1,
-            Dir::Right => *c += 1,
+            Dir::Down => Dir::Up,
+            Dir::Left => Dir::Right,
+            Dir::Up => Dir::Down,
+            Dir::Right => Dir::Left,
         }
     }
 
@@ -40,61 +52,316 @@ impl Dir {
     }
 }
 
+fn exits(pipe: u8) -> Option<[Dir; 2]> {
+    Some(match pipe {
+        b'|' => [Dir::Up, Dir::Down],
+        b'-' => [Dir::Left, Dir::Right],
+        b'L' => [Dir::Up, Dir::Right],
+        b'J' => [Dir::Up, Dir::Left],
+        b'7' => [Dir::Down, Dir::Left],
+        b'F' => [Dir::Down, Dir::Right],
+        _ => return None,
+    })
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MazeError {
+    NoStart,
+    // the walk ran off the edge of the grid
+    OutOfBounds {
+        pos: (usize, usize),
+        heading: Dir,
+    },
+    // the pipe at `pos` cannot be entered while heading this way
+    BrokenJoint {
+        pos: (usize, usize),
+        pipe: char,
+        heading: Dir,
+    },
+    NoLoop {
+        start: (usize, usize),
+    },
+    // more than one closed loop passes through the start
+    AmbiguousStart {
+        start: (usize, usize),
+        pipes: Vec<char>,
+    },
+}
+
+// Follows the pipes from `start` until returning to it, yielding the tiles
+// visited and the heading on arrival.
+fn walk(
+    grid: &[Vec<u8>],
+    start: (usize, usize),
+    mut dir: Dir,
+) -> Result<(Vec<(usize, usize)>, Dir), MazeError> {
+    let mut pos = start;
+    let mut path = vec![start];
+
+    loop {
+        pos = dir
+            .next_pos(pos, grid)
+            .ok_or(MazeError::OutOfBounds { pos, heading: dir })?;
+        if pos == start {
+            return Ok((path, dir));
+        }
+
+        path.push(pos);
+        let pipe = grid[pos.0][pos.1];
+        dir = dir.next_direction(pipe).ok_or(MazeError::BrokenJoint {
+            pos,
+            pipe: char::from(pipe),
+            heading: dir,
+        })?;
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Orientation {
+    Clockwise,
+    CounterClockwise,
+}
+
 #[derive(Debug)]
 pub struct Maze {
     pipe_loop: HashSet<(usize, usize)>,
+    // tiles of the loop in walking order, beginning at the start
+    path: Vec<(usize, usize)>,
     grid: Vec<Vec<u8>>,
 }
 
 impl Maze {
-    fn new(grid: Vec<Vec<u8>>, start: (usize, usize), mut dir: Dir) -> Self {
-        let (mut r, mut c) = start;
-        let mut pipe_loop = HashSet::new();
-        pipe_loop.insert((r, c));
-
-        loop {
-            dir.next_pos(&mut r, &mut c);
-            if (r, c) == start {
-                break;
+    fn new(grid: Vec<Vec<u8>>, path: Vec<(usize, usize)>) -> Self {
+        let mut pipe_loop = HashSet::with_capacity(path.len());
+        pipe_loop.extend(path.iter().copied());
+
+        Self {
+            pipe_loop,
+            path,
+            grid,
+        }
+    }
+
+    // Every closed loop of pipes in the grid, including the one through the start.
+    pub fn loops(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut seen = HashSet::new();
+        let mut loops = vec![];
+
+        for (r, row) in self.grid.iter().enumerate() {
+            for (c, &pipe) in row.iter().enumerate() {
+                let Some([a, b]) = exits(pipe) else {
+                    continue;
+                };
+                if !seen.insert((r, c)) {
+                    continue;
+                }
+
+                if let Ok((path, arrival)) = walk(&self.grid, (r, c), a)
+                    && arrival.opposite() == b
+                {
+                    seen.extend(path.iter().copied());
+                    loops.push(path);
+                }
             }
-            pipe_loop.insert((r, c));
-            dir = dir.next_direction(grid[r][c]).unwrap_or_else(|| {
-                panic!(
-                    "Unknown pipe combination {} {:?}",
-                    char::from(grid[r][c]),
-                    dir,
-                )
-            });
         }
 
-        Self { pipe_loop, grid }
+        loops
+    }
+
+    pub fn path(&self) -> &[(usize, usize)] {
+        &self.path
+    }
+
+    // The loop as a polygon: only the tiles where the pipe turns.
+    pub fn vertices(&self) -> Vec<(usize, usize)> {
+        self.path
+            .iter()
+            .copied()
+            .filter(|&(r, c)| b"LJ7F".contains(&self.grid[r][c]))
+            .collect()
+    }
+
+    // Shoelace sum over the loop with x = column and y = row; positive when the
+    // loop runs clockwise on screen.
+    fn shoelace(&self) -> i64 {
+        self.path
+            .iter()
+            .zip(self.path.iter().cycle().skip(1))
+            .map(|(&(r0, c0), &(r1, c1))| c0 as i64 * r1 as i64 - c1 as i64 * r0 as i64)
+            .sum()
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        if self.shoelace() > 0 {
+            Orientation::Clockwise
+        } else {
+            Orientation::CounterClockwise
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        self.shoelace().abs() as f64 / 2.0
+    }
+
+    // Pick's theorem: A = i + b/2 - 1
+    pub fn enclosed_area(&self) -> usize {
+        let area2 = self.shoelace().unsigned_abs() as usize;
+        (area2 + 2 - self.path.len()) / 2
+    }
+
+    // scan the row, checking to see if we are in or out of the loop
+    fn enclosed_in_row(&self, row: usize) -> impl Iterator<Item = usize> + '_ {
+        // we are on the left of the first column, so we can assume we're outside
+        let mut inside = false;
+
+        self.grid[row]
+            .iter()
+            .enumerate()
+            .filter_map(move |(col, cell)| {
+                if self.pipe_loop.contains(&(row, col)) {
+                    // If we see a vertical, then we flip our state
+                    // a "vertical" is |JL or |7F. We have to be consistent between
+                    // JL or 7F on which we consider "vertical" but either JL xor 7F work
+                    if b"|JL".contains(cell) {
+                        inside = !inside;
+                    }
+                    None
+                } else {
+                    inside.then_some(col)
+                }
+            })
+    }
+
+    pub fn enclosed_tiles(&self) -> Vec<(usize, usize)> {
+        (0..self.grid.len())
+            .flat_map(|row| self.enclosed_in_row(row).map(move |col| (row, col)))
+            .collect()
+    }
+
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let start = self.path[0];
+        let inside: HashSet<_> = self.enclosed_tiles().into_iter().collect();
+        let mut out = String::new();
+
+        for (r, row) in self.grid.iter().enumerate() {
+            for (c, &cell) in row.iter().enumerate() {
+                let (glyph, colour) = if (r, c) == start {
+                    (options.start, START_COLOUR)
+                } else if self.pipe_loop.contains(&(r, c)) {
+                    (box_glyph(cell), LOOP_COLOUR)
+                } else if inside.contains(&(r, c)) {
+                    (options.inside, INSIDE_COLOUR)
+                } else {
+                    (options.outside, OUTSIDE_COLOUR)
+                };
+
+                if options.colour {
+                    out.push_str(colour);
+                    out.push(glyph);
+                    out.push_str(RESET);
+                } else {
+                    out.push(glyph);
+                }
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
+    pub fn render_to_file(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        options: &RenderOptions,
+    ) -> std::io::Result<()> {
+        std::fs::write(path, self.render(options))
     }
 }
 
-fn get(grid: &[Vec<u8>], r: usize, c: usize) -> &u8 {
-    grid.get(r).and_then(|x| x.get(c)).unwrap_or(&b'.')
+const START_COLOUR: &str = "\x1b[1;31m";
+const LOOP_COLOUR: &str = "\x1b[1;34m";
+const INSIDE_COLOUR: &str = "\x1b[32m";
+const OUTSIDE_COLOUR: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+fn box_glyph(pipe: u8) -> char {
+    match pipe {
+        b'|' => '│',
+        b'-' => '─',
+        b'L' => '└',
+        b'J' => '┘',
+        b'7' => '┐',
+        b'F' => '┌',
+        _ => char::from(pipe),
+    }
 }
 
-fn determine_start(grid: &[Vec<u8>], r: usize, c: usize) -> (u8, Dir) {
-    let up = b"|7F".contains(get(grid, r.wrapping_sub(1), c));
-    let down = b"|LJ".contains(get(grid, r + 1, c));
-    let left = b"-FL".contains(get(grid, r, c.wrapping_sub(1)));
-    let right = b"-J7".contains(get(grid, r, c + 1));
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RenderOptions {
+    pub colour: bool,
+    pub start: char,
+    pub inside: char,
+    pub outside: char,
+}
 
-    match (up, down, left, right) {
-        (false, false, true, true) => (b'-', Dir::Right),
-        (false, true, false, true) => (b'F', Dir::Down),
-        (false, true, true, false) => (b'7', Dir::Down),
-        (true, false, false, true) => (b'L', Dir::Right),
-        (true, false, true, false) => (b'J', Dir::Up),
-        (true, true, false, false) => (b'|', Dir::Down),
-        _ => panic!("unknown piece {up} {down} {left} {right}"),
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            colour: false,
+            start: 'S',
+            inside: 'I',
+            outside: '·',
+        }
     }
 }
 
-#[aoc_generator(day10)]
-pub fn generator(input: &str) -> Maze {
-    let mut start: (usize, usize) = (0, 0);
+fn connects(grid: &[Vec<u8>], pos: (usize, usize), dir: Dir) -> bool {
+    dir.next_pos(pos, grid)
+        .and_then(|(r, c)| exits(grid[r][c]))
+        .is_some_and(|e| e.contains(&dir.opposite()))
+}
+
+// Tries every pipe shape for the start tile, keeping the ones whose walk closes
+// back on the start.
+fn determine_start(
+    grid: &mut [Vec<u8>],
+    start: (usize, usize),
+) -> Result<Vec<(usize, usize)>, MazeError> {
+    let mut closed = vec![];
+    let mut error = None;
+
+    for pipe in *b"|-LJ7F" {
+        let [a, b] = exits(pipe).unwrap();
+        if !connects(grid, start, a) || !connects(grid, start, b) {
+            continue;
+        }
+
+        grid[start.0][start.1] = pipe;
+        match walk(grid, start, a) {
+            Ok((path, arrival)) if arrival.opposite() == b => closed.push((pipe, path)),
+            Ok(_) => {}
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+
+    match closed.len() {
+        0 => Err(error.unwrap_or(MazeError::NoLoop { start })),
+        1 => {
+            let (pipe, path) = closed.pop().unwrap();
+            grid[start.0][start.1] = pipe;
+            Ok(path)
+        }
+        _ => Err(MazeError::AmbiguousStart {
+            start,
+            pipes: closed.iter().map(|&(pipe, _)| char::from(pipe)).collect(),
+        }),
+    }
+}
+
+pub fn parse(input: &str) -> Result<Maze, MazeError> {
+    let mut start = None;
 
     let mut grid: Vec<Vec<u8>> = input
         .lines()
@@ -104,7 +371,7 @@ pub fn generator(input: &str) -> Maze {
                 .enumerate()
                 .map(|(col, cell)| {
                     if cell == b'S' {
-                        start = (row, col)
+                        start = Some((row, col))
                     }
                     cell
                 })
@@ -112,10 +379,15 @@ pub fn generator(input: &str) -> Maze {
         })
         .collect();
 
-    let (start_pipe, start_dir) = determine_start(&grid, start.0, start.1);
-    grid[start.0][start.1] = start_pipe;
+    let start = start.ok_or(MazeError::NoStart)?;
+    let path = determine_start(&mut grid, start)?;
 
-    Maze::new(grid, start, start_dir)
+    Ok(Maze::new(grid, path))
+}
+
+#[aoc_generator(day10)]
+pub fn generator(input: &str) -> Maze {
+    parse(input).unwrap()
 }
 
 #[aoc(day10, part1)]
@@ -125,32 +397,9 @@ pub fn part1(maze: &Maze) -> usize {
 
 #[aoc(day10, part2)]
 pub fn part2(maze: &Maze) -> usize {
-    // scan row by row, checking to see if we are in or out of the loop
-    maze.grid
-        .par_iter()
-        .enumerate()
-        .map(|(row, line)| {
-            // we are on the left of the first column, so we can assume we're outside
-            let mut inside = false;
-            let mut count = 0;
-
-            for (col, cell) in line.iter().enumerate() {
-                if maze.pipe_loop.contains(&(row, col)) {
-                    // If we see a vertical, then we flip our state
-                    // a "vertical" is |JL or |7F. We have to be consistent between
-                    // JL or 7F on which we consider "vertical" but either JL xor 7F work
-                    if b"|JL".contains(cell) {
-                        inside = !inside;
-                    }
-                } else {
-                    // if we aren't part of the loop, then we just add the bool to our count
-                    // if we're inside we'll add 1, if we are outside 0
-                    count += usize::from(inside);
-                }
-            }
-
-            count
-        })
+    (0..maze.grid.len())
+        .into_par_iter()
+        .map(|row| maze.enclosed_in_row(row).count())
         .sum()
 }
 
@@ -192,6 +441,112 @@ L7JLJL-JLJLJL--JLJ.L";
         assert_eq!(part2(&generator(SAMPLE2)), 10);
     }
 
+    #[test]
+    pub fn parse_test() {
+        // the start could close either the inner or the outer loop
+        let maze = ".....
+.F7..
+.LS7.
+..LJ.
+.....";
+        assert_eq!(
+            parse(maze).unwrap_err(),
+            MazeError::AmbiguousStart {
+                start: (2, 2),
+                pipes: vec!['J', 'F']
+            }
+        );
+
+        // junk pipes and a second loop elsewhere in the grid
+        let maze = "-L|F7
+7S-7|
+L|7||
+-L-J|
+L|-JF
+F7...
+LJ...";
+        let maze = parse(maze).unwrap();
+        assert_eq!(maze.path().len(), 8);
+        assert_eq!(maze.loops().len(), 2);
+
+        assert_eq!(
+            parse(".S-7.\n.|.|.\n.L-..").unwrap_err(),
+            MazeError::BrokenJoint {
+                pos: (2, 3),
+                pipe: '.',
+                heading: Dir::Right
+            }
+        );
+        assert_eq!(parse(".S-7\n.|.|\n.L-J").unwrap().path().len(), 8);
+        assert_eq!(
+            parse("S-7").unwrap_err(),
+            MazeError::NoLoop { start: (0, 0) }
+        );
+        assert_eq!(parse("F-7").unwrap_err(), MazeError::NoStart);
+    }
+
+    #[test]
+    pub fn render_test() {
+        let maze = generator(SAMPLE);
+        assert_eq!(
+            maze.render(&RenderOptions::default()),
+            "··┌┐·
+·┌┘│·
+S┘I└┐
+│┌──┘
+└┘···
+"
+        );
+
+        let coloured = maze.render(&RenderOptions {
+            colour: true,
+            ..RenderOptions::default()
+        });
+        assert!(coloured.starts_with("\x1b[2m·\x1b[0m"));
+        assert!(coloured.contains("\x1b[1;31mS\x1b[0m"));
+        assert!(coloured.contains("\x1b[32mI\x1b[0m"));
+
+        let path =
+            std::env::temp_dir().join(format!("day10_render_test_{}.txt", std::process::id()));
+        maze.render_to_file(&path, &RenderOptions::default())
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            maze.render(&RenderOptions::default())
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    pub fn polygon_test() {
+        let maze = generator(SAMPLE);
+        assert_eq!(maze.path().len(), 16);
+        assert_eq!(maze.path()[..3], [(2, 0), (3, 0), (4, 0)]);
+        assert_eq!(maze.vertices().len(), 12);
+        assert_eq!(maze.orientation(), Orientation::CounterClockwise);
+        assert_eq!(maze.area(), 8.0);
+        assert_eq!(maze.enclosed_area(), 1);
+        assert_eq!(maze.enclosed_tiles(), [(2, 2)]);
+
+        let maze = generator(SAMPLE2);
+        assert_eq!(maze.enclosed_area(), 10);
+        assert_eq!(
+            maze.enclosed_tiles(),
+            [
+                (3, 14),
+                (4, 10),
+                (4, 11),
+                (4, 12),
+                (4, 13),
+                (5, 11),
+                (5, 12),
+                (5, 13),
+                (6, 13),
+                (6, 14)
+            ]
+        );
+    }
+
     mod regression {
         use super::*;
 
@@ -1,10 +1,90 @@
-use aoc_runner_derive::aoc;
+use aoc_runner_derive::{aoc, aoc_generator};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Universe {
+    // galaxies in reading order, before expansion
+    galaxies: Vec<(usize, usize)>,
+    // number of empty rows/columns strictly before each index
+    empty_rows: Vec<u64>,
+    empty_cols: Vec<u64>,
+    // galaxy rows and columns, each sorted
+    rows: Vec<usize>,
+    cols: Vec<usize>,
+}
+
+fn prefix_empty(seen: &[bool]) -> Vec<u64> {
+    seen.iter()
+        .scan(0, |count, &seen| {
+            let before = *count;
+            *count += u64::from(!seen);
+            Some(before)
+        })
+        .collect()
+}
+
+// Sum of |a - b| over all pairs of a sorted slice.
+fn pairwise_sum(sorted: impl Iterator<Item = u64>) -> u64 {
+    sorted
+        .enumerate()
+        .fold((0, 0), |(total, prefix), (k, x)| {
+            (total + x * k as u64 - prefix, prefix + x)
+        })
+        .0
+}
+
+impl Universe {
+    // Every empty row or column gains `expansion` extra copies of itself.
+    fn expand(prefix: &[u64], x: usize, expansion: u64) -> u64 {
+        x as u64 + prefix[x] * expansion
+    }
+
+    pub fn galaxies(&self, expansion: u64) -> Vec<(u64, u64)> {
+        self.galaxies
+            .iter()
+            .map(|&(r, c)| {
+                (
+                    Self::expand(&self.empty_rows, r, expansion),
+                    Self::expand(&self.empty_cols, c, expansion),
+                )
+            })
+            .collect()
+    }
+
+    pub fn distance(&self, i: usize, j: usize, expansion: u64) -> u64 {
+        let (r0, c0) = self.galaxies[i];
+        let (r1, c1) = self.galaxies[j];
+
+        Self::expand(&self.empty_rows, r0, expansion).abs_diff(Self::expand(
+            &self.empty_rows,
+            r1,
+            expansion,
+        )) + Self::expand(&self.empty_cols, c0, expansion).abs_diff(Self::expand(
+            &self.empty_cols,
+            c1,
+            expansion,
+        ))
+    }
+
+    pub fn total_distance(&self, expansion: u64) -> u64 {
+        let rows = self
+            .rows
+            .iter()
+            .map(|&r| Self::expand(&self.empty_rows, r, expansion));
+        let cols = self
+            .cols
+            .iter()
+            .map(|&c| Self::expand(&self.empty_cols, c, expansion));
+
+        pairwise_sum(rows) + pairwise_sum(cols)
+    }
+}
 
-fn parse_data<const EXP: usize>(s: &str) -> Vec<(usize, usize)> {
+#[aoc_generator(day11)]
+pub fn generator(s: &str) -> Universe {
     let width = s.lines().next().unwrap().len();
     let mut col_seen = vec![false; width];
     let mut row_seen = vec![];
-    let mut grid = Vec::new();
+    let mut galaxies = Vec::new();
 
     for (row, line) in s.lines().enumerate() {
         let mut seen = false;
@@ -12,57 +92,33 @@ fn parse_data<const EXP: usize>(s: &str) -> Vec<(usize, usize)> {
             if b == b'#' {
                 col_seen[col] = true;
                 seen = true;
-                grid.push((row, col));
+                galaxies.push((row, col));
             }
         }
         row_seen.push(seen);
     }
 
-    expand::<EXP, _>(&mut grid, &row_seen, |(galaxy, _)| galaxy);
-    expand::<EXP, _>(&mut grid, &col_seen, |(_, galaxy)| galaxy);
+    let rows = galaxies.iter().map(|&(r, _)| r).collect();
+    let mut cols: Vec<_> = galaxies.iter().map(|&(_, c)| c).collect();
+    cols.sort_unstable();
 
-    grid
-}
-
-fn expand<const EXP: usize, F>(grid: &mut [(usize, usize)], seen: &[bool], get: F)
-where
-    F: Fn(&mut (usize, usize)) -> &mut usize,
-{
-    let mut adjustment = 0;
-    for (row, seen) in seen.iter().enumerate() {
-        if !seen {
-            for galaxy in grid.iter_mut() {
-                if *get(galaxy) > row + adjustment {
-                    *get(galaxy) += EXP;
-                }
-            }
-            adjustment += EXP;
-        }
-    }
-}
-
-fn solve<const EXP: usize>(s: &str) -> usize {
-    let v = parse_data::<EXP>(s);
-
-    let mut sum = 0;
-    for i in 0..v.len() {
-        for j in i..v.len() {
-            sum += v[i].0.abs_diff(v[j].0);
-            sum += v[i].1.abs_diff(v[j].1);
-        }
+    Universe {
+        galaxies,
+        empty_rows: prefix_empty(&row_seen),
+        empty_cols: prefix_empty(&col_seen),
+        rows,
+        cols,
     }
-
-    sum
 }
 
 #[aoc(day11, part1)]
-pub fn part1(inputs: &str) -> usize {
-    solve::<1>(inputs)
+pub fn part1(universe: &Universe) -> u64 {
+    universe.total_distance(1)
 }
 
 #[aoc(day11, part2)]
-pub fn part2(inputs: &str) -> usize {
-    solve::<999999>(inputs)
+pub fn part2(universe: &Universe) -> u64 {
+    universe.total_distance(999999)
 }
 
 #[cfg(test)]
@@ -102,37 +158,55 @@ mod tests {
                 line.bytes()
                     .enumerate()
                     .filter(|(_, b)| b == &b'#')
-                    .map(move |(col, _)| (row, col))
+                    .map(move |(col, _)| (row as u64, col as u64))
             })
             .collect();
 
-        assert_eq!(parse_data::<1>(SAMPLE), input);
+        assert_eq!(generator(SAMPLE).galaxies(1), input);
     }
 
     #[test]
     pub fn part1_test() {
-        assert_eq!(part1(SAMPLE), 374);
+        assert_eq!(part1(&generator(SAMPLE)), 374);
     }
 
     #[test]
     pub fn part2_test() {
-        assert_eq!(solve::<9>(SAMPLE), 1030);
-        assert_eq!(solve::<99>(SAMPLE), 8410);
+        assert_eq!(generator(SAMPLE).total_distance(9), 1030);
+        assert_eq!(generator(SAMPLE).total_distance(99), 8410);
+    }
+
+    #[test]
+    pub fn distance_test() {
+        let universe = generator(SAMPLE);
+        assert_eq!(universe.distance(4, 8, 1), 9);
+        assert_eq!(universe.distance(0, 6, 1), 15);
+        assert_eq!(universe.distance(2, 5, 1), 17);
+        assert_eq!(universe.distance(7, 8, 1), 5);
+
+        for expansion in [0, 1, 9, 99, 999999] {
+            let n = universe.galaxies.len();
+            let brute: u64 = (0..n)
+                .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+                .map(|(i, j)| universe.distance(i, j, expansion))
+                .sum();
+            assert_eq!(universe.total_distance(expansion), brute);
+        }
     }
 
     mod regression {
         use super::*;
 
         const INPUT: &str = include_str!("../input/2023/day11.txt");
-        const ANSWERS: (usize, usize) = (10228230, 447073334102);
+        const ANSWERS: (u64, u64) = (10228230, 447073334102);
 
         #[test]
         pub fn test() {
             let input = INPUT.trim_end_matches('\n');
-            // let output = generator(input);
+            let output = generator(input);
 
-            assert_eq!(part1(input), ANSWERS.0);
-            assert_eq!(part2(input), ANSWERS.1);
+            assert_eq!(part1(&output), ANSWERS.0);
+            assert_eq!(part2(&output), ANSWERS.1);
         }
     }
 }
@@ -1,5 +1,5 @@
-use ahash::{HashMap, HashMapExt};
 use aoc_runner_derive::{aoc, aoc_generator};
+use num::{BigUint, One, Zero};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
 use crate::common::utils::parse_split;
@@ -39,71 +39,372 @@ pub struct Line {
 }
 
 impl Line {
-    fn solve(&self, dp: &mut HashMap<(usize, usize, usize), usize>) -> usize {
-        solve(&self.spring, &self.block, 0, dp)
+    pub fn arrangements<T>(&self) -> T
+    where
+        T: Clone + Zero + One,
+    {
+        ways::<T>(&self.spring, &self.block)
+            .swap_remove(0)
+            .swap_remove(0)
     }
 
-    fn expand(&self) -> Self {
-        let mut spring = Vec::with_capacity((self.spring.len() + 1) * 5);
-        let mut block = Vec::with_capacity(self.block.len() * 5);
-        for _ in 0..4 {
+    pub fn unfold(&self, factor: usize) -> Self {
+        let mut spring = Vec::with_capacity((self.spring.len() + 1) * factor);
+        let mut block = Vec::with_capacity(self.block.len() * factor);
+        for i in 0..factor {
+            if i > 0 {
+                spring.push(State::Unknown);
+            }
             spring.extend_from_slice(&self.spring);
-            spring.push(State::Unknown);
             block.extend_from_slice(&self.block);
         }
-        spring.extend_from_slice(&self.spring);
-        block.extend_from_slice(&self.block);
 
         Self { spring, block }
     }
+
+    fn block_end(&self, i: usize, j: usize) -> Option<usize> {
+        block_end(&self.spring, &self.block, i, j)
+    }
+
+    // Damages springs i..end, then marks `end` operational if it exists.
+    fn place_block(&self, i: usize, end: usize, states: &mut Vec<State>) -> usize {
+        states.extend(std::iter::repeat_n(State::Damaged, end - i));
+        if end < self.spring.len() {
+            states.push(State::Operational);
+        }
+        (end + 1).min(self.spring.len())
+    }
+
+    pub fn iter_arrangements(&self) -> Arrangements<'_> {
+        let ways = ways::<BigUint>(&self.spring, &self.block);
+        let stack = if ways[0][0].is_zero() {
+            vec![]
+        } else {
+            vec![(0, 0, Vec::with_capacity(self.spring.len()))]
+        };
+
+        Arrangements {
+            line: self,
+            ways,
+            stack,
+        }
+    }
+
+    // The arrangement at position `rank` in the order `iter_arrangements` yields
+    // them. A uniformly random rank below the count gives a uniform sample.
+    pub fn arrangement(&self, rank: &BigUint) -> Option<Vec<State>> {
+        let ways = ways::<BigUint>(&self.spring, &self.block);
+        if rank >= &ways[0][0] {
+            return None;
+        }
+
+        let mut rank = rank.clone();
+        let mut states = Vec::with_capacity(self.spring.len());
+        let (mut i, mut j) = (0, 0);
+        while i < self.spring.len() {
+            let operational = if self.spring[i] != State::Damaged {
+                ways[i + 1][j].clone()
+            } else {
+                BigUint::zero()
+            };
+
+            if rank < operational {
+                states.push(State::Operational);
+                i += 1;
+            } else {
+                rank -= operational;
+                let end = self.block_end(i, j)?;
+                i = self.place_block(i, end, &mut states);
+                j += 1;
+            }
+        }
+
+        Some(states)
+    }
+}
+
+pub struct Arrangements<'a> {
+    line: &'a Line,
+    ways: Vec<Vec<BigUint>>,
+    stack: Vec<(usize, usize, Vec<State>)>,
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<State>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.line;
+
+        // every entry on the stack leads to at least one arrangement
+        while let Some((i, j, mut states)) = self.stack.pop() {
+            if i == line.spring.len() {
+                return Some(states);
+            }
+
+            // pushed first so the operational branch is explored first
+            if let Some(end) = line.block_end(i, j) {
+                let mut states = states.clone();
+                let next = line.place_block(i, end, &mut states);
+                if !self.ways[next][j + 1].is_zero() {
+                    self.stack.push((next, j + 1, states));
+                }
+            }
+
+            if line.spring[i] != State::Damaged && !self.ways[i + 1][j].is_zero() {
+                states.push(State::Operational);
+                self.stack.push((i + 1, j, states));
+            }
+        }
+
+        None
+    }
+}
+
+pub fn render(states: &[State]) -> String {
+    states.iter().map(|s| format!("{s:?}")).collect()
+}
+
+// Where block j ends if it can start at spring i.
+fn block_end(spring: &[State], block: &[usize], i: usize, j: usize) -> Option<usize> {
+    let end = i + block.get(j)?;
+    let fits = spring[i] != State::Operational
+        && end <= spring.len()
+        && !spring[i..end].contains(&State::Operational)
+        && spring.get(end) != Some(&State::Damaged);
+
+    fits.then_some(end)
+}
+
+// ways[i][j] is the number of arrangements of spring[i..] using block[j..],
+// where position i is not continuing a run of damaged springs.
+fn ways<T>(spring: &[State], block: &[usize]) -> Vec<Vec<T>>
+where
+    T: Clone + Zero + One,
+{
+    let (n, m) = (spring.len(), block.len());
+
+    // operational[i] is the number of known operational springs before i
+    let operational: Vec<usize> = std::iter::once(0)
+        .chain(spring.iter().scan(0, |count, s| {
+            *count += usize::from(s == &State::Operational);
+            Some(*count)
+        }))
+        .collect();
+
+    let mut ways = vec![vec![T::zero(); m + 1]; n + 1];
+    ways[n][m] = T::one();
+
+    for i in (0..n).rev() {
+        for j in (0..=m).rev() {
+            let mut res = T::zero();
+
+            // treat spring i as operational
+            if spring[i] != State::Damaged {
+                res = res + ways[i + 1][j].clone();
+            }
+
+            // start block j at spring i, followed by an operational spring
+            if spring[i] != State::Operational && j < m {
+                let end = i + block[j];
+                if end <= n
+                    && operational[end] == operational[i]
+                    && spring.get(end) != Some(&State::Damaged)
+                {
+                    res = res + ways[(end + 1).min(n)][j + 1].clone();
+                }
+            }
+
+            ways[i][j] = res;
+        }
+    }
+
+    ways
+}
+
+// Counts saturated at one: whether any arrangement exists.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Feasible(bool);
+
+impl std::ops::Add for Feasible {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 || rhs.0)
+    }
+}
+
+impl std::ops::Mul for Feasible {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self(self.0 && rhs.0)
+    }
+}
+
+impl Zero for Feasible {
+    fn zero() -> Self {
+        Self(false)
+    }
+
+    fn is_zero(&self) -> bool {
+        !self.0
+    }
 }
 
-fn solve(
-    spring: &[State],
-    block: &[usize],
-    stride: usize,
-    dp: &mut HashMap<(usize, usize, usize), usize>,
-) -> usize {
-    if let Some(v) = dp.get(&(spring.len(), block.len(), stride)) {
-        return *v;
-    }
-
-    let mut res = 0;
-    if let Some(piece) = spring.first() {
-        // Try doing the count if the variant is Operational and Damaged
-        // If the piece if Unknown we do both
-        // If the piece is Operational or Damaged, then we do it once when the variant matches
-
-        // Damaged                             : increase stride
-        // Operational && empty stride         : continue processing
-        // Operational && stride == first block: next block, and reset stride
-        // Operational && anything else        : Invalid, return 0
-        // Unknown                             : can't happen (handled by the variant logic)
-        for variant in [State::Operational, State::Damaged] {
-            if piece == &variant || piece == &State::Unknown {
-                res += match variant {
-                    State::Damaged => solve(&spring[1..], block, stride + 1, dp),
-                    State::Operational if stride == 0 => solve(&spring[1..], block, 0, dp),
-                    State::Operational if block.first() == Some(&stride) => {
-                        solve(&spring[1..], &block[1..], 0, dp)
+impl One for Feasible {
+    fn one() -> Self {
+        Self(true)
+    }
+}
+
+// For each spring, whether some arrangement has it (damaged, operational).
+// Returns `None` when no arrangement fits at all.
+fn line_options(spring: &[State], block: &[usize]) -> Option<Vec<(bool, bool)>> {
+    let (n, m) = (spring.len(), block.len());
+    let ways = ways::<Feasible>(spring, block);
+    if ways[0][0].is_zero() {
+        return None;
+    }
+
+    let mut reachable = vec![vec![false; m + 1]; n + 1];
+    reachable[0][0] = true;
+    let mut operational = vec![false; n];
+    // difference array over the damaged runs
+    let mut damaged = vec![0i32; n + 1];
+
+    for i in 0..n {
+        for j in 0..=m {
+            if !reachable[i][j] {
+                continue;
+            }
+
+            if spring[i] != State::Damaged && !ways[i + 1][j].is_zero() {
+                operational[i] = true;
+                reachable[i + 1][j] = true;
+            }
+
+            if let Some(end) = block_end(spring, block, i, j) {
+                let next = (end + 1).min(n);
+                if !ways[next][j + 1].is_zero() {
+                    damaged[i] += 1;
+                    damaged[end] -= 1;
+                    if end < n {
+                        operational[end] = true;
                     }
-                    State::Operational => 0,
-                    State::Unknown => unreachable!(),
+                    reachable[next][j + 1] = true;
                 }
             }
         }
-    } else {
-        // if block is empty and stride is empty OR
-        // last block is the same as the stride
-        if block.is_empty() && stride == 0 || block.len() == 1 && stride == block[0] {
-            return 1;
-        } else {
-            return 0;
+    }
+
+    let damaged = damaged.iter().scan(0, |run, &d| {
+        *run += d;
+        Some(*run > 0)
+    });
+
+    Some(damaged.zip(operational).collect())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Solutions {
+    None,
+    Unique(Vec<Vec<State>>),
+    // two of the distinct solutions
+    Many(Vec<Vec<State>>, Vec<Vec<State>>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    cols: Vec<Vec<usize>>,
+}
+
+impl Nonogram {
+    pub fn new(rows: Vec<Vec<usize>>, cols: Vec<Vec<usize>>) -> Self {
+        Self { rows, cols }
+    }
+
+    // Narrows a single line, returning whether anything changed, or `None` on a
+    // contradiction.
+    fn deduce(line: &mut [State], block: &[usize]) -> Option<bool> {
+        let options = line_options(line, block)?;
+        let mut changed = false;
+
+        for (cell, option) in line.iter_mut().zip(options) {
+            let state = match option {
+                (true, true) => continue,
+                (true, false) => State::Damaged,
+                (false, true) => State::Operational,
+                (false, false) => return None,
+            };
+
+            if *cell == State::Unknown {
+                *cell = state;
+                changed = true;
+            }
         }
+
+        Some(changed)
     }
 
-    dp.insert((spring.len(), block.len(), stride), res);
-    res
+    // Applies the line solver to every row and column until nothing changes.
+    fn propagate(&self, grid: &mut [Vec<State>]) -> Option<()> {
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for (row, block) in grid.iter_mut().zip(&self.rows) {
+                changed |= Self::deduce(row, block)?;
+            }
+
+            for (c, block) in self.cols.iter().enumerate() {
+                let mut col: Vec<_> = grid.iter().map(|row| row[c].clone()).collect();
+                if Self::deduce(&mut col, block)? {
+                    changed = true;
+                    for (row, cell) in grid.iter_mut().zip(col) {
+                        row[c] = cell;
+                    }
+                }
+            }
+        }
+
+        Some(())
+    }
+
+    fn search(&self, mut grid: Vec<Vec<State>>, found: &mut Vec<Vec<Vec<State>>>) {
+        if found.len() >= 2 || self.propagate(&mut grid).is_none() {
+            return;
+        }
+
+        let unknown = grid.iter().enumerate().find_map(|(r, row)| {
+            let c = row.iter().position(|cell| cell == &State::Unknown)?;
+            Some((r, c))
+        });
+
+        match unknown {
+            None => found.push(grid),
+            Some((r, c)) => {
+                for guess in [State::Damaged, State::Operational] {
+                    let mut grid = grid.clone();
+                    grid[r][c] = guess;
+                    self.search(grid, found);
+                }
+            }
+        }
+    }
+
+    pub fn solve(&self) -> Solutions {
+        let grid = vec![vec![State::Unknown; self.cols.len()]; self.rows.len()];
+        let mut found = vec![];
+        self.search(grid, &mut found);
+
+        let mut found = found.into_iter();
+        match (found.next(), found.next()) {
+            (None, _) => Solutions::None,
+            (Some(grid), None) => Solutions::Unique(grid),
+            (Some(a), Some(b)) => Solutions::Many(a, b),
+        }
+    }
 }
 
 #[aoc_generator(day12)]
@@ -123,17 +424,14 @@ pub fn generator(input: &str) -> Vec<Line> {
 
 #[aoc(day12, part1)]
 pub fn part1(inputs: &[Line]) -> usize {
-    inputs
-        .par_iter()
-        .map(|l| l.solve(&mut HashMap::new()))
-        .sum()
+    inputs.par_iter().map(Line::arrangements::<usize>).sum()
 }
 
 #[aoc(day12, part2)]
 pub fn part2(inputs: &[Line]) -> usize {
     inputs
         .par_iter()
-        .map(|l| l.expand().solve(&mut HashMap::new()))
+        .map(|l| l.unfold(5).arrangements::<usize>())
         .sum()
 }
 
@@ -164,6 +462,103 @@ mod tests {
         assert_eq!(part2(&generator(SAMPLE)), 525152);
     }
 
+    #[test]
+    pub fn arrangements_test() {
+        let lines = generator(SAMPLE);
+        assert_eq!(
+            lines[0]
+                .iter_arrangements()
+                .map(|a| render(&a))
+                .collect::<Vec<_>>(),
+            ["#.#.###"]
+        );
+        assert_eq!(
+            lines[5]
+                .iter_arrangements()
+                .map(|a| render(&a))
+                .take(3)
+                .collect::<Vec<_>>(),
+            [".###....##.#", ".###...##..#", ".###...##.#."]
+        );
+
+        for line in &lines {
+            let all = line.iter_arrangements().collect::<Vec<_>>();
+            assert_eq!(all.len(), line.arrangements::<usize>());
+            for (rank, arrangement) in all.iter().enumerate() {
+                assert_eq!(
+                    line.arrangement(&BigUint::from(rank)).as_ref(),
+                    Some(arrangement)
+                );
+            }
+            assert_eq!(line.arrangement(&BigUint::from(all.len())), None);
+        }
+
+        let unfolded = lines[5].unfold(5);
+        let rank = unfolded.arrangements::<BigUint>() - 1u8;
+        assert_eq!(
+            render(&unfolded.arrangement(&rank).unwrap()),
+            ".###.##.#.....###.##.#.....###.##.#.....###.##.#.....###.##.#..."
+        );
+    }
+
+    #[test]
+    pub fn nonogram_test() {
+        let clues = vec![vec![1, 1, 1], vec![3], vec![5], vec![3], vec![1, 1, 1]];
+        let Solutions::Unique(grid) = Nonogram::new(clues.clone(), clues).solve() else {
+            panic!("expected a unique solution");
+        };
+        assert_eq!(
+            grid.iter().map(|row| render(row)).collect::<Vec<_>>(),
+            ["#.#.#", ".###.", "#####", ".###.", "#.#.#"]
+        );
+
+        let Solutions::Many(a, b) =
+            Nonogram::new(vec![vec![1], vec![1]], vec![vec![1], vec![1]]).solve()
+        else {
+            panic!("expected many solutions");
+        };
+        assert_ne!(a, b);
+
+        assert_eq!(
+            Nonogram::new(vec![vec![2]], vec![vec![1], vec![]]).solve(),
+            Solutions::None
+        );
+
+        let options = line_options(&parse_spring("??.??#"), &[1, 2]).unwrap();
+        assert_eq!(
+            options,
+            [
+                (true, true),
+                (true, true),
+                (false, true),
+                (false, true),
+                (true, false),
+                (true, false)
+            ]
+        );
+    }
+
+    #[test]
+    pub fn unfold_test() {
+        let lines = generator(SAMPLE);
+        assert_eq!(
+            lines
+                .iter()
+                .map(|l| l.unfold(1).arrangements::<usize>())
+                .collect::<Vec<_>>(),
+            [1, 4, 1, 1, 4, 10]
+        );
+        assert_eq!(lines[0].unfold(0).arrangements::<usize>(), 1);
+        assert_eq!(lines[5].unfold(5).arrangements::<usize>(), 506250);
+
+        let big = lines[5].unfold(30).arrangements::<BigUint>();
+        assert!(big > BigUint::from(u64::MAX));
+        assert_eq!(
+            lines[1].unfold(40).arrangements::<BigUint>(),
+            lines[1].unfold(40).arrangements::<u128>().into()
+        );
+    }
+
     mod regression {
         use super::*;
 
@@ -2,61 +2,176 @@ use std::convert::Infallible;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use crate::common::{matrix::rotate_right_m_n, utils::parse_split};
+use crate::common::utils::parse_split;
+
+// One bitmask per line, `words` u64s each, so lines of any length fit.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Lines {
+    words: usize,
+    data: Vec<u64>,
+}
+
+impl Lines {
+    fn new(count: usize, len: usize, get: impl Fn(usize, usize) -> bool) -> Self {
+        let words = len.div_ceil(64).max(1);
+        let mut data = vec![0; count * words];
+
+        for line in 0..count {
+            for i in (0..len).filter(|&i| get(line, i)) {
+                data[line * words + i / 64] |= 1 << (i % 64);
+            }
+        }
+
+        Self { words, data }
+    }
+
+    fn line(&self, i: usize) -> &[u64] {
+        &self.data[i * self.words..(i + 1) * self.words]
+    }
+
+    fn distance(&self, a: usize, b: usize) -> usize {
+        self.line(a)
+            .iter()
+            .zip(self.line(b))
+            .map(|(a, b)| (a ^ b).count_ones() as usize)
+            .sum()
+    }
+
+    // Positions within the line where `a` and `b` differ.
+    fn differences(&self, a: usize, b: usize) -> impl Iterator<Item = usize> + '_ {
+        self.line(a)
+            .iter()
+            .zip(self.line(b))
+            .enumerate()
+            .flat_map(|(w, (a, b))| {
+                let mut diff = a ^ b;
+                std::iter::from_fn(move || {
+                    (diff != 0).then(|| {
+                        let bit = diff.trailing_zeros() as usize;
+                        diff &= diff - 1;
+                        w * 64 + bit
+                    })
+                })
+            })
+    }
+}
 
 #[derive(Debug)]
 pub struct Pattern {
-    pattern: Vec<Vec<u8>>,
+    height: usize,
+    width: usize,
+    rows: Lines,
+    cols: Lines,
 }
 
 impl std::str::FromStr for Pattern {
     type Err = Infallible;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let pattern: Vec<_> = s.lines().map(|line| line.as_bytes().to_vec()).collect();
-        Ok(Pattern { pattern })
+        let pattern: Vec<_> = s.lines().map(str::as_bytes).collect();
+        let height = pattern.len();
+        let width = pattern.first().map_or(0, |row| row.len());
+        let rock = |r: usize, c: usize| pattern[r][c] == b'#';
+
+        Ok(Pattern {
+            height,
+            width,
+            rows: Lines::new(height, width, rock),
+            cols: Lines::new(width, height, |c, r| rock(r, c)),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Axis {
+    // number of rows above the line
+    Horizontal(usize),
+    // number of columns left of the line
+    Vertical(usize),
+}
+
+impl Axis {
+    pub fn summary(&self) -> usize {
+        match self {
+            Axis::Horizontal(rows) => rows * 100,
+            Axis::Vertical(cols) => *cols,
+        }
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Reflection {
+    pub axis: Axis,
+    pub mismatches: usize,
+    // for each mismatched pair, the (row, col) of the cell above or left of the axis
+    pub flips: Vec<(usize, usize)>,
+}
+
 impl Pattern {
-    fn find_mirror<const TARGET: usize>(&self) -> Option<usize> {
-        let a = Self::find_mirror_horz::<TARGET>(&self.pattern);
+    // Pairs of lines mirrored across a split after `split` lines.
+    fn mirrored(len: usize, split: usize) -> impl Iterator<Item = (usize, usize)> {
+        (0..split.min(len - split)).map(move |k| (split - 1 - k, split + k))
+    }
 
-        if let Some(a) = a {
-            Some(a * 100)
-        } else {
-            Self::find_mirror_vert::<TARGET>(&self.pattern)
+    // Total distance across the split, giving up once it exceeds `limit`.
+    fn mismatches(lines: &Lines, len: usize, split: usize, limit: usize) -> usize {
+        Self::mirrored(len, split)
+            .try_fold(0, |sum, (a, b)| {
+                let sum = sum + lines.distance(a, b);
+                (sum <= limit).then_some(sum)
+            })
+            .unwrap_or(limit + 1)
+    }
+
+    fn reflection(&self, axis: Axis) -> Reflection {
+        let mut flips: Vec<_> = match axis {
+            Axis::Horizontal(split) => Self::mirrored(self.height, split)
+                .flat_map(|(a, b)| self.rows.differences(a, b).map(move |c| (a, c)))
+                .collect(),
+            Axis::Vertical(split) => Self::mirrored(self.width, split)
+                .flat_map(|(a, b)| self.cols.differences(a, b).map(move |r| (r, a)))
+                .collect(),
+        };
+        flips.sort_unstable();
+
+        Reflection {
+            axis,
+            mismatches: flips.len(),
+            flips,
         }
     }
 
-    fn count_badness<const TARGET: usize>(left: &[u8], right: &[u8]) -> usize {
-        left.iter()
-            .zip(right.iter())
-            .filter(|(l, r)| l != r)
-            .try_fold(0, |sum, _| (sum < TARGET).then_some(sum + 1))
-            .unwrap_or(TARGET + 1)
+    fn candidates(&self, limit: usize) -> impl Iterator<Item = (Axis, usize)> + '_ {
+        let horizontal = (1..self.height).map(move |split| {
+            let count = Self::mismatches(&self.rows, self.height, split, limit);
+            (Axis::Horizontal(split), count)
+        });
+        let vertical = (1..self.width).map(move |split| {
+            let count = Self::mismatches(&self.cols, self.width, split, limit);
+            (Axis::Vertical(split), count)
+        });
+
+        horizontal.chain(vertical)
     }
 
-    fn check_mirror<const TARGET: usize>(left: &[Vec<u8>], right: &[Vec<u8>]) -> bool {
-        left.iter()
-            .rev()
-            .zip(right.iter())
-            .map(|(l, r)| Self::count_badness::<TARGET>(l, r))
-            .try_fold(0, |sum, n| (sum + n <= TARGET).then_some(sum + n))
-            .unwrap_or(TARGET + 1)
-            == TARGET
+    // Every candidate axis, horizontal ones first, with its exact mismatch count.
+    pub fn axes(&self) -> Vec<Reflection> {
+        self.candidates(usize::MAX - 1)
+            .map(|(axis, _)| self.reflection(axis))
+            .collect()
     }
 
-    fn find_mirror_horz<const TARGET: usize>(p: &[Vec<u8>]) -> Option<usize> {
-        (1..p.len()).find(|&split| {
-            let (left, right) = p.split_at(split);
-            Self::check_mirror::<TARGET>(left, right)
-        })
+    pub fn reflections(&self, smudges: usize) -> Vec<Reflection> {
+        self.candidates(smudges)
+            .filter(|&(_, count)| count == smudges)
+            .map(|(axis, _)| self.reflection(axis))
+            .collect()
     }
 
-    fn find_mirror_vert<const TARGET: usize>(p: &[Vec<u8>]) -> Option<usize> {
-        let p = rotate_right_m_n(p);
-        Self::find_mirror_horz::<TARGET>(&p)
+    fn find_mirror(&self, smudges: usize) -> Option<usize> {
+        self.candidates(smudges)
+            .find(|&(_, count)| count == smudges)
+            .map(|(axis, _)| axis.summary())
     }
 }
 
@@ -67,12 +182,12 @@ pub fn generator(input: &str) -> Vec<Pattern> {
 
 #[aoc(day13, part1)]
 pub fn part1(inputs: &[Pattern]) -> usize {
-    inputs.iter().filter_map(Pattern::find_mirror::<0>).sum()
+    inputs.iter().filter_map(|p| p.find_mirror(0)).sum()
 }
 
 #[aoc(day13, part2)]
 pub fn part2(inputs: &[Pattern]) -> usize {
-    inputs.iter().filter_map(Pattern::find_mirror::<1>).sum()
+    inputs.iter().filter_map(|p| p.find_mirror(1)).sum()
 }
 
 #[cfg(test)]
@@ -100,8 +215,8 @@ mod tests {
         let p = generator(SAMPLE);
         // println!("{:?}", p);
 
-        assert_eq!(Pattern::find_mirror_vert::<0>(&p[0].pattern), Some(5));
-        assert_eq!(Pattern::find_mirror_horz::<0>(&p[1].pattern), Some(4));
+        assert_eq!(p[0].reflections(0)[0].axis, Axis::Vertical(5));
+        assert_eq!(p[1].reflections(0)[0].axis, Axis::Horizontal(4));
     }
 
     #[test]
@@ -114,6 +229,67 @@ mod tests {
         assert_eq!(part2(&generator(SAMPLE)), 400);
     }
 
+    #[test]
+    pub fn smudge_test() {
+        let p = generator(SAMPLE);
+        assert_eq!(p[0].axes().len(), 6 + 8);
+
+        assert_eq!(
+            p[0].reflections(1),
+            [Reflection {
+                axis: Axis::Horizontal(3),
+                mismatches: 1,
+                flips: vec![(0, 0)],
+            }]
+        );
+        assert_eq!(
+            p[1].reflections(1),
+            [Reflection {
+                axis: Axis::Horizontal(1),
+                mismatches: 1,
+                flips: vec![(0, 4)],
+            }]
+        );
+        // columns 0 and 1 differ in rows 0 and 6
+        assert_eq!(
+            p[0].reflections(2),
+            [Reflection {
+                axis: Axis::Vertical(1),
+                mismatches: 2,
+                flips: vec![(0, 0), (6, 0)],
+            }]
+        );
+    }
+
+    #[test]
+    pub fn wide_test() {
+        // a 130-wide pattern mirrored after column 70, with one smudge past bit 64
+        let half: String = (0..60).map(|i| if i % 7 < 3 { '#' } else { '.' }).collect();
+        let mirrored: String = half.chars().rev().collect();
+        let row = format!("{}{half}{mirrored}", ".".repeat(10));
+        let mut smudged = row.clone();
+        smudged.replace_range(100..101, if &row[100..101] == "#" { "." } else { "#" });
+
+        let p: Pattern = format!("{row}\n{row}").parse().unwrap();
+        assert_eq!(p.rows.words, 3);
+        let axes: Vec<_> = p.reflections(0).iter().map(|r| r.axis).collect();
+        assert!(axes.contains(&Axis::Horizontal(1)));
+        assert!(axes.contains(&Axis::Vertical(70)));
+
+        let p: Pattern = format!("{row}\n{smudged}").parse().unwrap();
+        let reflections = p.reflections(1);
+        assert!(reflections.contains(&Reflection {
+            axis: Axis::Horizontal(1),
+            mismatches: 1,
+            flips: vec![(0, 100)],
+        }));
+        assert!(reflections.contains(&Reflection {
+            axis: Axis::Vertical(70),
+            mismatches: 1,
+            flips: vec![(1, 39)],
+        }));
+    }
+
     mod regression {
         use super::*;
 
@@ -4,108 +4,219 @@ use rustc_hash::FxHashMap as HashMap;
 
 const TARGET: usize = 1_000_000_000;
 
+// Bitset over the dish, with cell (r, c) at bit `r * stride + c`.
+#[derive(PartialEq, Eq, Hash, Clone)]
+struct Board(Vec<u64>);
+
+impl Board {
+    fn set(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(|&w| w == 0)
+    }
+
+    // Moves every bit `by` places towards bit 0, or away from it when negative.
+    fn shift(&self, by: isize) -> Self {
+        let (q, r) = (by.unsigned_abs() / 64, by.unsigned_abs() % 64);
+        let word = |i: Option<usize>| i.and_then(|i| self.0.get(i)).copied().unwrap_or(0);
+
+        Self(
+            (0..self.0.len())
+                .map(|i| {
+                    if by >= 0 {
+                        let lo = word(i.checked_add(q)) >> r;
+                        let hi = if r == 0 {
+                            0
+                        } else {
+                            word(i.checked_add(q + 1)) << (64 - r)
+                        };
+                        lo | hi
+                    } else {
+                        let hi = word(i.checked_sub(q)) << r;
+                        let lo = if r == 0 {
+                            0
+                        } else {
+                            word(i.checked_sub(q + 1)) >> (64 - r)
+                        };
+                        hi | lo
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(w, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                (word != 0).then(|| {
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    w * 64 + bit
+                })
+            })
+        })
+    }
+}
+
+// Each row is followed by a padding column, which along with the bits past
+// the last row is marked as a wall so rocks can't roll between rows.
 #[derive(PartialEq, Eq, Hash, Clone)]
 pub struct Dish {
-    grid: Vec<u8>,
+    round: Board,
+    walls: Board,
+    stride: usize,
     width: usize,
     height: usize,
 }
 
 impl std::fmt::Debug for Dish {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in self.grid.chunks(self.width) {
-            writeln!(f, "{}", String::from_utf8_lossy(row))?;
+        for r in 0..self.height {
+            for i in (r * self.stride..).take(self.width) {
+                let c = match (self.round.get(i), self.walls.get(i)) {
+                    (true, _) => 'O',
+                    (_, true) => '#',
+                    _ => '.',
+                };
+                write!(f, "{c}")?;
+            }
+            writeln!(f)?;
         }
 
         Ok(())
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Tilt {
+    North,
+    West,
+    South,
+    East,
+}
+
+impl Tilt {
+    pub fn parse_program(s: &str) -> Option<Vec<Self>> {
+        s.chars()
+            .map(|c| match c {
+                'N' => Some(Tilt::North),
+                'W' => Some(Tilt::West),
+                'S' => Some(Tilt::South),
+                'E' => Some(Tilt::East),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
 impl Dish {
-    fn roll(
-        &mut self,
-        x: impl IntoIterator<Item = usize>,
-        y: impl IntoIterator<Item = usize> + Clone,
-        f: impl Fn(usize, usize) -> usize,
-        offset: u8,
-    ) {
-        let mut available_slots = [u8::MAX; 100];
-        for x in x {
-            for y in y.clone() {
-                let idx = f(x, y);
-                let slot = &mut available_slots[y];
-
-                match self.grid[idx] {
-                    b'.' if *slot == u8::MAX => *slot = x as u8,
-                    b'#' => *slot = u8::MAX,
-                    b'O' if *slot != u8::MAX => {
-                        self.grid[idx] = b'.';
-                        self.grid[f(usize::from(*slot), y)] = b'O';
-                        *slot = slot.wrapping_add(offset);
-                    }
-                    _ => {}
-                }
+    // Every rock that can move takes one step at once, until none can.
+    pub fn tilt(&mut self, tilt: Tilt) {
+        let by = match tilt {
+            Tilt::North => self.stride as isize,
+            Tilt::South => -(self.stride as isize),
+            Tilt::West => 1,
+            Tilt::East => -1,
+        };
+
+        loop {
+            let moved = Board(
+                self.round
+                    .shift(by)
+                    .0
+                    .iter()
+                    .zip(&self.round.0)
+                    .zip(&self.walls.0)
+                    .map(|((s, r), w)| s & !(r | w))
+                    .collect(),
+            );
+            if moved.is_empty() {
+                break;
+            }
+
+            let from = moved.shift(-by);
+            for ((r, f), m) in self.round.0.iter_mut().zip(&from.0).zip(&moved.0) {
+                *r = (*r & !f) | m;
             }
         }
     }
 
-    fn roll_north(&mut self) {
-        let w = self.width;
-        self.roll(0..self.height, 0..w, |r, c| r * w + c, 1);
+    pub fn run(&mut self, program: &[Tilt]) {
+        for &tilt in program {
+            self.tilt(tilt);
+        }
     }
 
-    fn roll_west(&mut self) {
-        let w = self.width;
-        self.roll(0..w, 0..self.height, |c, r| r * w + c, 1);
-    }
+    // Runs `program` `iterations` times, skipping ahead once the dish repeats.
+    pub fn spin(&mut self, program: &[Tilt], iterations: usize) {
+        let mut seen = HashMap::with_capacity(256);
 
-    fn roll_south(&mut self) {
-        let w = self.width;
-        let h = self.height;
-        // 0u8.wrapping_sub(1) is -1
-        self.roll((0..h).rev(), 0..w, |r, c| r * w + c, 0u8.wrapping_sub(1));
-    }
+        let mut t = 0;
+        while t < iterations {
+            t += 1;
+            self.run(program);
 
-    fn roll_east(&mut self) {
-        let w = self.width;
-        let h = self.height;
-        self.roll((0..w).rev(), 0..h, |c, r| r * w + c, 0u8.wrapping_sub(1));
-    }
+            if let Some(old) = seen.get(&self.round) {
+                let cyc = t - old;
+                let amt = (iterations - t) / cyc;
+                t += amt * cyc;
+            }
 
-    fn cycle(&mut self) {
-        self.roll_north();
-        self.roll_west();
-        self.roll_south();
-        self.roll_east();
+            seen.insert(self.round.clone(), t);
+        }
     }
 
-    fn load(&self) -> usize {
-        let mut sum = 0;
-        for row in 0..self.height {
-            let mult = self.height - row;
-
-            let row_offset = row * self.width;
-            for col in 0..self.width {
-                if self.grid[row_offset + col] == b'O' {
-                    sum += mult;
+    // Load on the support beams along `edge`.
+    pub fn load(&self, edge: Tilt) -> usize {
+        self.round
+            .ones()
+            .map(|i| {
+                let (row, col) = (i / self.stride, i % self.stride);
+                match edge {
+                    Tilt::North => self.height - row,
+                    Tilt::South => row + 1,
+                    Tilt::West => self.width - col,
+                    Tilt::East => col + 1,
                 }
-            }
-        }
-        sum
+            })
+            .sum()
     }
 }
 
 #[aoc_generator(day14)]
 pub fn generator(input: &str) -> Dish {
-    let width = input.find('\n').unwrap();
-    let height = input.len() / width;
-    let mut grid = Vec::with_capacity(width * height);
-    for line in input.lines() {
-        grid.extend(line.bytes());
+    let width = input.lines().next().unwrap().len();
+    let height = input.lines().count();
+    let stride = width + 1;
+    let words = (height * stride).div_ceil(64).max(1);
+
+    let mut round = Board(vec![0; words]);
+    let mut walls = Board(vec![0; words]);
+    for (r, line) in input.lines().enumerate() {
+        for (c, b) in line.bytes().enumerate() {
+            match b {
+                b'O' => round.set(r * stride + c),
+                b'#' => walls.set(r * stride + c),
+                _ => {}
+            }
+        }
+        walls.set(r * stride + width);
+    }
+    for i in height * stride..words * 64 {
+        walls.set(i);
     }
 
     Dish {
-        grid,
+        round,
+        walls,
+        stride,
         width,
         height,
     }
@@ -115,30 +226,16 @@ pub fn generator(input: &str) -> Dish {
 pub fn part1(platform: &Dish) -> usize {
     let mut platform = platform.clone();
 
-    platform.roll_north();
-    platform.load()
+    platform.tilt(Tilt::North);
+    platform.load(Tilt::North)
 }
 
 #[aoc(day14, part2)]
 pub fn part2(platform: &Dish) -> usize {
     let mut platform = platform.clone();
-    let mut seen = HashMap::with_capacity(256);
-
-    let mut t = 0;
-    while t < TARGET {
-        t += 1;
-        platform.cycle();
-
-        if let Some(old) = seen.get(&platform.grid) {
-            let cyc = t - old;
-            let amt = (TARGET - t) / cyc;
-            t += amt * cyc;
-        }
 
-        seen.insert(platform.grid.clone(), t);
-    }
-
-    platform.load()
+    platform.spin(&Tilt::parse_program("NWSE").unwrap(), TARGET);
+    platform.load(Tilt::North)
 }
 
 #[cfg(test)]
@@ -160,7 +257,7 @@ O.#..O.#.#
     pub fn input_test() {
         let mut platform = generator(SAMPLE);
         println!("{:?}", platform);
-        platform.roll_north();
+        platform.tilt(Tilt::North);
         println!("{:?}", platform);
 
         // push_left(&mut platform.dish[9]);
@@ -183,6 +280,64 @@ O.#..O.#.#
         assert_eq!(part2(&generator(SAMPLE)), 64);
     }
 
+    #[test]
+    pub fn program_test() {
+        let dish = generator(SAMPLE);
+        assert_eq!(Tilt::parse_program("NX"), None);
+
+        let mut once = dish.clone();
+        once.spin(&Tilt::parse_program("NWSE").unwrap(), 1);
+        assert_eq!(
+            format!("{once:?}"),
+            ".....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....
+"
+        );
+
+        let mut west = generator("O.#O\n..O.");
+        west.run(&Tilt::parse_program("WW").unwrap());
+        assert_eq!(format!("{west:?}"), "O.#O\nO...\n");
+        assert_eq!(west.load(Tilt::West), 4 + 1 + 4);
+        assert_eq!(west.load(Tilt::East), 1 + 4 + 1);
+
+        // taller than wide, and beyond the old fixed limits
+        let tall = format!("{}#", "O\n.\n".repeat(150));
+        let mut tall = generator(&tall);
+        assert_eq!(tall.height, 301);
+        tall.tilt(Tilt::South);
+        assert_eq!(tall.load(Tilt::South), (151..=300).sum());
+        tall.tilt(Tilt::North);
+        assert_eq!(tall.load(Tilt::North), (152..=301).sum());
+
+        let wide = format!(
+            "O{}#{}O\n{}O",
+            ".".repeat(70),
+            ".".repeat(70),
+            ".".repeat(142)
+        );
+        let mut wide = generator(&wide);
+        wide.run(&Tilt::parse_program("ES").unwrap());
+        assert_eq!(
+            format!("{wide:?}"),
+            format!(
+                "{}#{}O\n{}O{}O\n",
+                ".".repeat(71),
+                ".".repeat(70),
+                ".".repeat(70),
+                ".".repeat(71)
+            )
+        );
+        assert_eq!(wide.load(Tilt::East), 71 + 143 + 143);
+    }
+
     mod regression {
         use super::*;
 
@@ -1,57 +1,241 @@
-use aoc_runner_derive::aoc;
+use aoc_runner_derive::{aoc, aoc_generator};
 
-type Vec<T> = arrayvec::ArrayVec<T, 8>;
+const BOXES: usize = 256;
 
 fn calc_hash(s: &str) -> usize {
     s.bytes()
         .fold(0, |current, n| (current + usize::from(n)) * 17 % 256)
 }
 
-// #[aoc_generator(day15)]
-// this is here so the tests don't need to refer directly to part1 and part2 without the generator
-pub fn generator(s: &str) -> &str {
-    s
+// The lens library from the HASHMAP procedure: 256 boxes, each an ordered
+// list of labelled lenses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashmapBoxes {
+    boxes: Vec<Vec<(String, usize)>>,
 }
 
-#[aoc(day15, part1)]
-pub fn part1(input: &str) -> usize {
-    input.split(',').map(calc_hash).sum()
+impl Default for HashmapBoxes {
+    fn default() -> Self {
+        Self {
+            boxes: vec![Vec::new(); BOXES],
+        }
+    }
 }
 
-#[aoc(day15, part2)]
-pub fn part2(inputs: &str) -> usize {
-    let mut boxes: [_; 256] = std::array::from_fn(|_| Vec::new());
-    for operation in inputs.split(',') {
-        if let Some(label) = operation.strip_suffix('-') {
-            let hash = calc_hash(label);
-            if let Some(pos) = boxes[hash].iter().position(|(l, _)| *l == label) {
-                boxes[hash].remove(pos);
+impl HashmapBoxes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn slot(&self, label: &str) -> (usize, Option<usize>) {
+        let hash = calc_hash(label);
+        (hash, self.boxes[hash].iter().position(|(l, _)| l == label))
+    }
+
+    // Returns the focal length of the lens that was replaced, if any.
+    pub fn insert(&mut self, label: &str, focal_length: usize) -> Option<usize> {
+        match self.slot(label) {
+            (hash, Some(pos)) => Some(std::mem::replace(
+                &mut self.boxes[hash][pos].1,
+                focal_length,
+            )),
+            (hash, None) => {
+                self.boxes[hash].push((label.to_string(), focal_length));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, label: &str) -> Option<usize> {
+        let (hash, pos) = self.slot(label);
+        pos.map(|pos| self.boxes[hash].remove(pos).1)
+    }
+
+    pub fn get(&self, label: &str) -> Option<usize> {
+        let (hash, pos) = self.slot(label);
+        pos.map(|pos| self.boxes[hash][pos].1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.boxes.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.iter().all(Vec::is_empty)
+    }
+
+    // (box, slot, label, focal length) in box then slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &str, usize)> {
+        self.boxes.iter().enumerate().flat_map(|(box_num, lenses)| {
+            lenses
+                .iter()
+                .enumerate()
+                .map(move |(slot, (label, focal))| (box_num, slot, label.as_str(), *focal))
+        })
+    }
+
+    pub fn focusing_power(&self) -> usize {
+        self.iter()
+            .map(|(box_num, slot, _, focal)| (box_num + 1) * (slot + 1) * focal)
+            .sum()
+    }
+
+    pub fn apply(&mut self, operation: &Operation) {
+        match operation {
+            Operation::Remove(label) => {
+                self.remove(label);
             }
-        } else if let Some((label, focal_length)) = operation.split_once('=') {
-            let focal_length: usize = focal_length.parse().unwrap();
-            let hash = calc_hash(label);
-
-            if let Some(fl) = boxes[hash]
-                .iter_mut()
-                .find_map(|(l, fl)| (*l == label).then_some(fl))
-            {
-                *fl = focal_length;
-            } else {
-                boxes[hash].push((label, focal_length));
+            Operation::Insert(label, focal_length) => {
+                self.insert(label, *focal_length);
             }
-        } else {
-            panic!("unknown input `{operation}`");
         }
     }
 
-    let mut sum = 0;
-    for (box_num, v) in boxes.iter().enumerate() {
-        for (slot_num, (_, focal)) in v.iter().enumerate() {
-            sum += (box_num + 1) * (slot_num + 1) * focal;
+    // The state of the boxes after each operation.
+    pub fn snapshots<'a>(
+        mut self,
+        operations: impl IntoIterator<Item = &'a Operation>,
+    ) -> impl Iterator<Item = Self> {
+        operations.into_iter().map(move |operation| {
+            self.apply(operation);
+            self.clone()
+        })
+    }
+}
+
+impl<'a> Extend<&'a Operation> for HashmapBoxes {
+    fn extend<T: IntoIterator<Item = &'a Operation>>(&mut self, operations: T) {
+        for operation in operations {
+            self.apply(operation);
+        }
+    }
+}
+
+// Formatted like the puzzle's walkthrough, listing only non-empty boxes.
+impl std::fmt::Display for HashmapBoxes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (box_num, lenses) in self.boxes.iter().enumerate() {
+            if lenses.is_empty() {
+                continue;
+            }
+
+            write!(f, "Box {box_num}:")?;
+            for (label, focal) in lenses {
+                write!(f, " [{label} {focal}]")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Operation {
+    Remove(String),
+    Insert(String, usize),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Step {
+    pub text: String,
+    pub operation: Operation,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StepErrorKind {
+    Empty,
+    // labels are lowercase letters
+    InvalidLabel,
+    // neither `label-` nor `label=N`
+    UnknownOperation,
+    // focal lengths are a single digit from 1 to 9
+    InvalidFocalLength,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StepError {
+    // byte offset of the start of the step in the original input
+    pub offset: usize,
+    pub step: String,
+    pub kind: StepErrorKind,
+}
+
+impl std::fmt::Display for StepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind {
+            StepErrorKind::Empty => "empty step",
+            StepErrorKind::InvalidLabel => "label must be lowercase letters",
+            StepErrorKind::UnknownOperation => "expected `label-` or `label=N`",
+            StepErrorKind::InvalidFocalLength => "focal length must be 1-9",
+        };
+        write!(f, "step `{}` at byte {}: {reason}", self.step, self.offset)
+    }
+}
+
+fn parse_operation(step: &str) -> Result<Operation, StepErrorKind> {
+    let (label, operation) = if let Some(label) = step.strip_suffix('-') {
+        (label, Operation::Remove(label.to_string()))
+    } else if let Some((label, focal_length)) = step.split_once('=') {
+        let focal_length = match focal_length.as_bytes() {
+            [d @ b'1'..=b'9'] => usize::from(d - b'0'),
+            _ => return Err(StepErrorKind::InvalidFocalLength),
+        };
+        (label, Operation::Insert(label.to_string(), focal_length))
+    } else if step.is_empty() {
+        return Err(StepErrorKind::Empty);
+    } else {
+        return Err(StepErrorKind::UnknownOperation);
+    };
+
+    if label.is_empty() || !label.bytes().all(|b| b.is_ascii_lowercase()) {
+        return Err(StepErrorKind::InvalidLabel);
+    }
+
+    Ok(operation)
+}
+
+// Splits the sequence on commas, ignoring newlines, and reports every bad step.
+pub fn parse(input: &str) -> Result<Vec<Step>, Vec<StepError>> {
+    let mut steps = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut offset = 0;
+    for raw in input.split(',') {
+        let text: String = raw.chars().filter(|&c| c != '\n' && c != '\r').collect();
+        match parse_operation(&text) {
+            Ok(operation) => steps.push(Step { text, operation }),
+            Err(kind) => errors.push(StepError {
+                offset,
+                step: text,
+                kind,
+            }),
         }
+        offset += raw.len() + 1;
+    }
+
+    if errors.is_empty() {
+        Ok(steps)
+    } else {
+        Err(errors)
     }
+}
+
+#[aoc_generator(day15)]
+pub fn generator(input: &str) -> Vec<Step> {
+    parse(input).unwrap()
+}
+
+#[aoc(day15, part1)]
+pub fn part1(steps: &[Step]) -> usize {
+    steps.iter().map(|step| calc_hash(&step.text)).sum()
+}
 
-    sum
+#[aoc(day15, part2)]
+pub fn part2(steps: &[Step]) -> usize {
+    let mut boxes = HashmapBoxes::new();
+    boxes.extend(steps.iter().map(|step| &step.operation));
+    boxes.focusing_power()
 }
 
 #[cfg(test)]
@@ -70,12 +254,104 @@ mod tests {
 
     #[test]
     pub fn part1_test() {
-        assert_eq!(part1(SAMPLE), 1320);
+        assert_eq!(part1(&generator(SAMPLE)), 1320);
     }
 
     #[test]
     pub fn part2_test() {
-        assert_eq!(part2(SAMPLE), 145);
+        assert_eq!(part2(&generator(SAMPLE)), 145);
+    }
+
+    #[test]
+    pub fn parse_test() {
+        assert_eq!(
+            generator("rn=1,c\nm-\n"),
+            [
+                Step {
+                    text: "rn=1".to_string(),
+                    operation: Operation::Insert("rn".to_string(), 1)
+                },
+                Step {
+                    text: "cm-".to_string(),
+                    operation: Operation::Remove("cm".to_string())
+                },
+            ]
+        );
+
+        let errors = parse("rn=1,cm=0,,qp,ot=10,Ab-,=3,pc=x").unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.offset, e.kind))
+                .collect::<Vec<_>>(),
+            [
+                (5, StepErrorKind::InvalidFocalLength),
+                (10, StepErrorKind::Empty),
+                (11, StepErrorKind::UnknownOperation),
+                (14, StepErrorKind::InvalidFocalLength),
+                (20, StepErrorKind::InvalidLabel),
+                (24, StepErrorKind::InvalidLabel),
+                (27, StepErrorKind::InvalidFocalLength),
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "step `cm=0` at byte 5: focal length must be 1-9"
+        );
+    }
+
+    #[test]
+    pub fn boxes_test() {
+        let operations: Vec<_> = generator(SAMPLE)
+            .into_iter()
+            .map(|step| step.operation)
+            .collect();
+        let snapshots: Vec<_> = HashmapBoxes::new()
+            .snapshots(&operations)
+            .map(|b| b.to_string())
+            .collect();
+        assert_eq!(snapshots[0], "Box 0: [rn 1]\n");
+        assert_eq!(snapshots[1], "Box 0: [rn 1]\n");
+        assert_eq!(snapshots[4], "Box 0: [rn 1] [cm 2]\n");
+        assert_eq!(
+            snapshots[10],
+            "Box 0: [rn 1] [cm 2]\nBox 3: [ot 7] [ab 5] [pc 6]\n"
+        );
+
+        let mut boxes = HashmapBoxes::new();
+        boxes.extend(&operations);
+        assert_eq!(boxes.len(), 5);
+        assert_eq!(boxes.get("ot"), Some(7));
+        assert_eq!(boxes.get("qp"), None);
+        assert_eq!(
+            boxes
+                .iter()
+                .map(|(b, s, l, _)| (b, s, l))
+                .collect::<Vec<_>>(),
+            [
+                (0, 0, "rn"),
+                (0, 1, "cm"),
+                (3, 0, "ot"),
+                (3, 1, "ab"),
+                (3, 2, "pc")
+            ]
+        );
+
+        assert_eq!(boxes.insert("ab", 1), Some(5));
+        assert_eq!(boxes.remove("rn"), Some(1));
+        assert_eq!(boxes.remove("rn"), None);
+
+        // more than eight lenses in one box
+        let mut crowded = HashmapBoxes::new();
+        let labels: Vec<_> = (0..10_000)
+            .map(|i| format!("l{i}"))
+            .filter(|l| calc_hash(l) == 0)
+            .collect();
+        assert!(labels.len() > 8);
+        for label in &labels {
+            crowded.insert(label, 1);
+        }
+        assert_eq!(crowded.focusing_power(), (1..=labels.len()).sum());
     }
 
     mod regression {
@@ -89,8 +365,8 @@ mod tests {
             let input = INPUT.trim_end_matches('\n');
             let output = generator(input);
 
-            assert_eq!(part1(output), ANSWERS.0);
-            assert_eq!(part2(output), ANSWERS.1);
+            assert_eq!(part1(&output), ANSWERS.0);
+            assert_eq!(part2(&output), ANSWERS.1);
         }
     }
 }
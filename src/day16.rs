@@ -1,5 +1,5 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use rayon::iter::{ParallelBridge, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 #[repr(u8)]
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -72,69 +72,149 @@ impl Dir {
     }
 }
 
-#[aoc_generator(day16)]
-pub fn generator(input: &str) -> Vec<Vec<Space>> {
-    input
-        .lines()
-        .map(|line| unsafe { std::mem::transmute::<&[u8], &[Space]>(line.as_bytes()) }.to_vec())
-        .collect()
+pub type Beam = ((usize, usize), Dir);
+
+// A straight run of the beam, `from` and `to` inclusive.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Segment {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub dir: Dir,
+}
+
+#[derive(Debug)]
+pub struct Contraption {
+    grid: Vec<Vec<Space>>,
 }
 
-fn solve(inputs: &[Vec<Space>], start: ((usize, usize), Dir)) -> usize {
-    let mut queue = Vec::new();
-    let mut seen = vec![vec![0; inputs[0].len()]; inputs.len()];
+impl Contraption {
+    fn get(&self, (y, x): (usize, usize)) -> Option<&Space> {
+        self.grid.get(y).and_then(|row| row.get(x))
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.len()
+    }
 
-    queue.push(start);
+    pub fn width(&self) -> usize {
+        self.grid.first().map_or(0, Vec::len)
+    }
 
-    while let Some((pos, dir)) = queue.pop() {
-        if let Some(kind) = inputs.get(pos.0).and_then(|row| row.get(pos.1)) {
-            if seen[pos.0][pos.1] & dir.get_mask() > 0 {
-                continue;
-            }
+    // Segments in the order they're traced, following the first branch of a
+    // split before the second.
+    pub fn trace(&self, start: Beam) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let mut queue = vec![start];
+        let mut seen = vec![vec![0; self.width()]; self.height()];
 
-            seen[pos.0][pos.1] |= dir.get_mask();
-            match kind {
-                Space::Empty => queue.push((dir.next_pos(pos), dir)),
-                Space::Slash | Space::BackSlash => {
-                    let new_dir = kind.next_dir(&dir);
-                    queue.push((new_dir.next_pos(pos), new_dir));
+        while let Some((from, dir)) = queue.pop() {
+            let mut pos = from;
+            let mut to = None;
+
+            while let Some(kind) = self.get(pos) {
+                if seen[pos.0][pos.1] & dir.get_mask() > 0 {
+                    break;
                 }
-                Space::Pipe | Space::Dash => {
-                    for next in kind.next_split(dir, pos).into_iter().flatten() {
-                        queue.push(next);
+
+                seen[pos.0][pos.1] |= dir.get_mask();
+                to = Some(pos);
+                match kind {
+                    Space::Empty => {}
+                    Space::Slash | Space::BackSlash => {
+                        let new_dir = kind.next_dir(&dir);
+                        queue.push((new_dir.next_pos(pos), new_dir));
+                        break;
+                    }
+                    Space::Pipe | Space::Dash => {
+                        if let [Some(first), Some(second)] = kind.next_split(dir, pos) {
+                            queue.push(second);
+                            queue.push(first);
+                            break;
+                        }
+                    }
+                    Space::NewLine => {
+                        unreachable!("shouldn't be processing newline")
                     }
                 }
-                Space::NewLine => {
-                    unreachable!("shouldn't be processing newline")
-                }
+                pos = dir.next_pos(pos);
+            }
+
+            if let Some(to) = to {
+                segments.push(Segment { from, to, dir });
+            }
+        }
+
+        segments
+    }
+
+    pub fn energised(&self, start: Beam) -> Vec<Vec<bool>> {
+        let mut energised = vec![vec![false; self.width()]; self.height()];
+
+        for Segment { from, to, dir } in self.trace(start) {
+            let mut pos = from;
+            energised[pos.0][pos.1] = true;
+            while pos != to {
+                pos = dir.next_pos(pos);
+                energised[pos.0][pos.1] = true;
             }
         }
+
+        energised
+    }
+
+    pub fn energised_count(&self, start: Beam) -> usize {
+        self.energised(start)
+            .iter()
+            .map(|row| row.iter().filter(|&&x| x).count())
+            .sum()
+    }
+
+    pub fn entries(&self) -> Vec<Beam> {
+        let height = self.height();
+        let width = self.width();
+
+        (0..height)
+            .map(|r| ((r, 0), Dir::East))
+            .chain((0..height).map(|r| ((r, width - 1), Dir::West)))
+            .chain((0..width).map(|c| ((0, c), Dir::South)))
+            .chain((0..width).map(|c| ((height - 1, c), Dir::North)))
+            .collect()
+    }
+
+    // Energised count for every entry along the edges, in `entries` order.
+    pub fn edge_table(&self) -> Vec<(Beam, usize)> {
+        self.entries()
+            .into_par_iter()
+            .map(|entry| (entry, self.energised_count(entry)))
+            .collect()
     }
 
-    seen.iter()
-        .map(|row| row.iter().filter(|&&x| x > 0).count())
-        .sum()
+    // The first entry with the most energised tiles.
+    pub fn best(&self) -> Option<(Beam, usize)> {
+        self.edge_table()
+            .into_iter()
+            .reduce(|best, x| if x.1 > best.1 { x } else { best })
+    }
+}
+
+#[aoc_generator(day16)]
+pub fn generator(input: &str) -> Contraption {
+    let grid = input
+        .lines()
+        .map(|line| unsafe { std::mem::transmute::<&[u8], &[Space]>(line.as_bytes()) }.to_vec())
+        .collect();
+
+    Contraption { grid }
 }
 
 #[aoc(day16, part1)]
-pub fn part1(inputs: &[Vec<Space>]) -> usize {
-    solve(inputs, ((0, 0), Dir::East))
+pub fn part1(contraption: &Contraption) -> usize {
+    contraption.energised_count(((0, 0), Dir::East))
 }
 
 #[aoc(day16, part2)]
-pub fn part2(inputs: &[Vec<Space>]) -> usize {
-    let height = inputs.len();
-    let width = inputs[0].len();
-
-    (0..height)
-        .map(|r| ((r, 0), Dir::East))
-        .chain((0..height).map(|r| ((r, width - 1), Dir::West)))
-        .chain((0..width).map(|c| ((0, c), Dir::South)))
-        .chain((0..width).map(|c| ((height - 1, c), Dir::North)))
-        .par_bridge()
-        .map(|x| solve(inputs, x))
-        .max()
-        .unwrap()
+pub fn part2(contraption: &Contraption) -> usize {
+    contraption.best().unwrap().1
 }
 
 #[cfg(test)]
@@ -156,7 +236,10 @@ mod tests {
     pub fn input_test() {
         println!("{:?}", generator(SAMPLE));
 
-        println!("{}", solve(&generator(SAMPLE), ((0, 3), Dir::South)));
+        println!(
+            "{}",
+            generator(SAMPLE).energised_count(((0, 3), Dir::South))
+        );
 
         // assert_eq!(generator(SAMPLE), Object());
     }
@@ -171,6 +254,52 @@ mod tests {
         assert_eq!(part2(&generator(SAMPLE)), 51);
     }
 
+    #[test]
+    pub fn contraption_test() {
+        let contraption = generator(SAMPLE);
+
+        let map: Vec<String> = contraption
+            .energised(((0, 0), Dir::East))
+            .iter()
+            .map(|row| row.iter().map(|&e| if e { '#' } else { '.' }).collect())
+            .collect();
+        assert_eq!(
+            map.join("\n"),
+            r"######....
+.#...#....
+.#...#####
+.#...##...
+.#...##...
+.#...##...
+.#..####..
+########..
+.#######..
+.#...#.#.."
+        );
+
+        let segments = contraption.trace(((0, 0), Dir::East));
+        assert_eq!(
+            segments[..2],
+            [
+                Segment {
+                    from: (0, 0),
+                    to: (0, 1),
+                    dir: Dir::East
+                },
+                Segment {
+                    from: (1, 1),
+                    to: (7, 1),
+                    dir: Dir::South
+                },
+            ]
+        );
+
+        let table = contraption.edge_table();
+        assert_eq!(table.len(), 40);
+        assert_eq!(table[0], (((0, 0), Dir::East), 46));
+        assert_eq!(contraption.best(), Some((((0, 3), Dir::South), 51)));
+    }
+
     mod regression {
         use super::*;
 